@@ -4,11 +4,14 @@ use anyhow::Result;
 use regex::Regex;
 use which::which;
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
 use chrono::*;
 
 use crate::util::*;
 use crate::taskbox::*;
+use crate::journal;
+use crate::cli::{OutputFormat, BoxLevel};
 
 pub fn browse() -> Result<()> {
     if cfg!(windows) {
@@ -61,8 +64,9 @@ pub fn edit_box(cur_box: &str, diffwith: Option<String>) {
     let tb = TaskBox::new(boxpath.clone());
     if tb.encrypted {
         println!("cannot edit {}box, plz decrypt first", S_failure!(LOCKED));
-        std::process::exit(1);
+        journal::exit(1);
     }
+    journal::track(&boxpath);
 
     if let Some(other) = diffwith {
         let otherf = if other.ends_with(".md") {
@@ -71,6 +75,7 @@ pub fn edit_box(cur_box: &str, diffwith: Option<String>) {
             &format!("{}/{}.md", Config_get!("basedir"), get_box_unalias(&other))
         };
 
+        journal::track(Path::new(otherf));
         println!("editing : {} v.s. {}", S_fpath!(boxpath.display()), S_fpath!(otherf));
         if run_cmd!(
             vimdiff $boxpath $otherf 2>/dev/null
@@ -99,7 +104,7 @@ pub fn edit_box(cur_box: &str, diffwith: Option<String>) {
     }
 }
 
-pub fn list_boxes(basedir_only: bool) {
+pub fn list_boxes(basedir_only: bool, format: OutputFormat) {
     let basedir = Config_get!("basedir");
 
     if basedir_only {
@@ -107,8 +112,6 @@ pub fn list_boxes(basedir_only: bool) {
         return
     }
 
-    println!("[ {} ]", S_fpath!(basedir));
-
     let mut boxes = Vec::new();
     for entry in std::fs::read_dir(&basedir).expect("cannot read dir") {
         let path = entry.expect("cannot get entry").path();
@@ -121,6 +124,36 @@ pub fn list_boxes(basedir_only: bool) {
         }
     }
     boxes.sort_by(|a,b| b.0.cmp(&a.0));
+
+    if format != OutputFormat::Text {
+        // no count for encrypted ones, to avoid asking for password
+        let boxes :Vec<_> = boxes.into_iter().map(|(boxname, encrypted)| {
+            let fpath = Path::new(&basedir).join(&boxname)
+                            .with_extension(if encrypted { "mdx" } else { "md" });
+            BoxJson {
+                schema: JSON_SCHEMA_VERSION,
+                alias: get_box_alias(&boxname),
+                encrypted,
+                path: fpath.display().to_string(),
+                count: (!encrypted).then(|| TaskBox::new(fpath).count()),
+                tasks: None,
+                name: boxname,
+            }
+        }).collect();
+
+        if format == OutputFormat::Ndjson {
+            boxes.iter().for_each(|b| println!("{}", serde_json::to_string(b).unwrap()))
+        } else {
+            println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+                "schema": JSON_SCHEMA_VERSION,
+                "basedir": basedir,
+                "boxes": boxes,
+            })).unwrap())
+        }
+        return
+    }
+
+    println!("[ {} ]", S_fpath!(basedir));
     boxes.into_iter().for_each(
         |(boxname, encrypted)| {
             if encrypted {
@@ -139,32 +172,356 @@ pub fn list_boxes(basedir_only: bool) {
         })
 }
 
+// list the tasks matched by #tags/@contexts in all boxes(except encrypted ones)
+pub fn list_matched_boxes(listall: bool, format: OutputFormat, filter: &TaskFilter, by_priority: bool) {
+    let basedir = Path::new(&Config_get!("basedir")).to_path_buf();
+
+    let mut boxes = Vec::new();
+    for path in box_files(&basedir, false) {
+        let mut tb = TaskBox::new(path);
+        if tb.has_matched(filter, listall) { boxes.push(tb) }
+    }
+
+    match format {
+        OutputFormat::Text => {
+            if boxes.is_empty() {
+                println!(" {} matched!", S_empty!("nothing"));
+            }
+            for mut tb in boxes {
+                let alias = get_box_alias(&tb.tbname);
+                print!("{}  {}", S_checkbox!(TASKBOX), tb.tbname);
+                if alias != tb.tbname {
+                    println!(" ({})", S_hints!(alias))
+                } else {
+                    println!()
+                }
+                tb.list(listall, false, false, filter, by_priority);
+                println!();
+            }
+        }
+        OutputFormat::Ndjson => boxes.iter_mut().for_each(|tb| tb.list_json(listall, format, filter)),
+        OutputFormat::Json => {
+            let boxes :Vec<_> = boxes.iter_mut().map(|tb| tb.to_json_filtered(listall, filter)).collect();
+            println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+                "schema": JSON_SCHEMA_VERSION,
+                "basedir": basedir,
+                "boxes": boxes,
+            })).unwrap())
+        }
+    }
+}
+
+/// date boxes of all levels in basedir, with their first/last dates, sorted by first date reversely
+pub fn period_boxes() -> Vec<(BoxLevel, NaiveDate, NaiveDate, PathBuf)> {
+    let mut boxes = Vec::new();
+    for entry in std::fs::read_dir(Config_get!("basedir")).expect("cannot read dir") {
+        let path = entry.expect("cannot get entry").path();
+        if ! path.is_file() || path.extension() != Some(OsStr::new("md")) { continue }
+        if let Some((level, first, last)) = box_period(path.file_stem().unwrap().to_str().unwrap()) {
+            boxes.push((level, first, last, path))
+        }
+    }
+    boxes.sort_by_key(|b| std::cmp::Reverse((b.1, b.2)));
+    boxes
+}
+
+/// daily boxes named as "YYYY-MM-DD.md" in basedir, sorted by date reversely
+pub fn date_boxes() -> Vec<(NaiveDate, PathBuf)> {
+    period_boxes().into_iter()
+        .filter(|(level, ..)| *level == BoxLevel::Day)
+        .map(|(_, date, _, path)| (date, path))
+        .collect()
+}
+
+// tasks due in the coming days(and overdue ones) of all boxes,
+// with the ones in the daily boxes of these days
+pub fn agenda(days: i64) {
+    let basedir = Path::new(&Config_get!("basedir")).to_path_buf();
+    let today = today_date();
+    let end = today + Duration::days(days);
+
+    let dated :HashMap<PathBuf, NaiveDate> = date_boxes().into_iter().map(|(d, p)| (p, d)).collect();
+    let mut agenda :BTreeMap<NaiveDate, Vec<String>> = BTreeMap::new();
+    for path in box_files(&basedir, false) {
+        let boxdate = dated.get(&path).copied().filter(|&d| d >= today);
+        let mut tb = TaskBox::new(path);
+        let alias = get_box_alias(&tb.tbname);
+
+        for item in tb.search(|_| true, Some(false)) {
+            let task = tb.get(item.id).unwrap();
+            let due = task.due.as_ref().and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+            let Some(date) = due.or(boxdate) else { continue };
+            if date >= end { continue }
+
+            let glyph = task.status.glyph().unwrap_or(CHECKBOX);
+            agenda.entry(date).or_default()
+                  .push(format!("   {}  {} ({})", S_checkbox!(glyph), task.text, S_hints!(alias)));
+        }
+    }
+
+    if agenda.is_empty() {
+        println!(" {} in the coming {} days!", S_empty!("nothing"), days);
+        return
+    }
+
+    let mut overdue_shown = false;
+    for (date, tasks) in agenda {
+        if date < today {
+            if ! overdue_shown {
+                println!("{} {}", S_failure!(WEEKLINE), S_failure!("overdue"));
+                overdue_shown = true;
+            }
+        } else {
+            let boxname = date.format("%Y-%m-%d").to_string();
+            let alias = get_box_alias(&boxname);
+            print!("{} {} {}", S_checkbox!(WEEKLINE), boxname, date.format("%a"));
+            if alias != boxname {
+                println!(" ({})", S_hints!(alias))
+            } else {
+                println!()
+            }
+        }
+        tasks.iter().for_each(|t| println!("{}", t));
+    }
+}
+
+// escape text value of iCalendar, by RFC 5545
+fn ics_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+// occurrences of routines in the coming days, as a calendar separated by weeks
+pub fn upcoming_routines(days: i64, format: OutputFormat, ics: bool) {
+    let today = today_date();
+    let mut tb = TaskBox::new(get_inbox_file(ROUTINE_BOXNAME));
+    let occurs = tb.occurrences(today, days);
+
+    if ics {
+        let stamp = now_utc().format("%Y%m%dT%H%M%SZ");
+        print!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//todor//routines//EN\r\n");
+        for occur in &occurs {
+            let date = NaiveDate::parse_from_str(&occur.date, "%Y-%m-%d").unwrap();
+            print!("BEGIN:VEVENT\r\nUID:{}-{}@todor\r\nDTSTAMP:{}\r\n", date.format("%Y%m%d"), occur.id, stamp);
+            print!("DTSTART;VALUE=DATE:{}\r\nDTEND;VALUE=DATE:{}\r\n",
+                   date.format("%Y%m%d"), (date + Duration::days(1)).format("%Y%m%d"));
+            print!("SUMMARY:{}\r\nCATEGORIES:{}\r\nEND:VEVENT\r\n", ics_escape(&occur.text), ics_escape(&occur.kind));
+        }
+        print!("END:VCALENDAR\r\n");
+        return
+    }
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+            "schema": JSON_SCHEMA_VERSION,
+            "from": today.to_string(),
+            "days": days,
+            "occurrences": occurs,
+        })).unwrap()),
+        OutputFormat::Ndjson => for occur in &occurs {
+            let mut line = serde_json::to_value(occur).unwrap();
+            line["schema"] = JSON_SCHEMA_VERSION.into();
+            println!("{}", line)
+        }
+        OutputFormat::Text => {
+            if occurs.is_empty() {
+                println!(" {} in the coming {} days!", S_empty!("nothing"), days);
+                return
+            }
+
+            let mut last :Option<NaiveDate> = None;
+            for occur in &occurs {
+                let date = NaiveDate::parse_from_str(&occur.date, "%Y-%m-%d").unwrap();
+                if last != Some(date) {
+                    if last.is_none_or(|last| last.iso_week() != date.iso_week()) {
+                        println!("{} {}", S_hints!(WEEKLINE), S_hints!(date.format("week %V")));
+                    }
+                    let alias = get_box_alias(&occur.date);
+                    print!("  {} {}", occur.date, date.format("%a"));
+                    if alias != occur.date {
+                        println!(" ({})", S_hints!(alias))
+                    } else {
+                        println!()
+                    }
+                    last = Some(date);
+                }
+                println!("     {}  {} ({})", S_checkbox!(ROUTINES), occur.text, S_hints!(occur.kind));
+            }
+        }
+    }
+}
+
+struct SearchHit {
+    bidx: usize,
+    id: usize,
+    label: String,
+}
+
+impl std::fmt::Display for SearchHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+// box files in dir, sorted by name reversely
+fn box_files(dir: &Path, encrypted: bool) -> Vec<PathBuf> {
+    let mut boxes = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else { return boxes };
+    for entry in entries {
+        let path = entry.expect("cannot get entry").path();
+        if path.is_file() && (path.extension() == Some(OsStr::new("md")) ||
+                              encrypted && path.extension() == Some(OsStr::new("mdx"))) {
+            boxes.push(path)
+        }
+    }
+    boxes.sort_by(|a,b| b.file_stem().cmp(&a.file_stem()));
+    boxes
+}
+
+// search tasks in all boxes(and archived ones), done: Some(true) for done only, Some(false) for open only
+pub fn search(pattern: &str, fuzzy: bool, done: Option<bool>,
+              archives: bool, encrypted: bool, interactive: bool) {
+    let matches :Box<dyn Fn(&str) -> bool> = if fuzzy {
+        let pattern = pattern.to_string();
+        Box::new(move |text| fuzzy_match(&pattern, text))
+    } else {
+        let re = Regex::new(pattern).unwrap_or_else(|_| {
+            println!("{} {}", S_failure!("invalid regex:"), pattern);
+            journal::exit(1)
+        });
+        Box::new(move |text| re.is_match(text))
+    };
+
+    let basedir = Path::new(&Config_get!("basedir")).to_path_buf();
+    let mut paths = box_files(&basedir, encrypted);
+    if archives {
+        paths.extend(box_files(&basedir.join("archives"), encrypted))
+    }
+
+    let mut boxes = Vec::new();
+    let mut hits = Vec::new();
+    for path in paths {
+        let archived = path.parent() != Some(basedir.as_path());
+        let mut tb = TaskBox::new(path);
+        let found = tb.search(&matches, done);
+        if found.is_empty() { continue }
+
+        let boxname = if archived { format!("archives/{}", tb.tbname) } else { tb.tbname.clone() };
+        let alias = get_box_alias(&tb.tbname);
+        if tb.encrypted {
+            print!("{} ", S_warning!(LOCKED));
+        } else {
+            print!("  ");
+        }
+        print!("{}  {}", S_checkbox!(TASKBOX), boxname);
+        if alias != tb.tbname {
+            println!(" ({})", S_hints!(alias))
+        } else {
+            println!()
+        }
+
+        for item in found {
+            let task = tb.get(item.id).unwrap();
+            if task.is_done() {
+                println!("    {}  {}", S_checked!(CHECKED), item.label.strikethrough())
+            } else {
+                println!("    {}  {}", S_checkbox!(CHECKBOX), item.label);
+                hits.push(SearchHit { bidx: boxes.len(), id: item.id,
+                                      label: format!("{}: {}", alias, item.label.trim_start()) });
+            }
+        }
+        boxes.push(tb);
+    }
+
+    if boxes.is_empty() {
+        println!(" {} matched!", S_empty!("nothing"));
+        return
+    }
+    if ! interactive { return }
+
+    if hits.is_empty() {
+        println!(" {} left to mark or move!", S_empty!("nothing"));
+        return
+    }
+    let selected = i_select(hits, "choose tasks:");
+    if selected.is_empty() { return }
+
+    const TO_MARK :&str = "mark as done";
+    const TO_MOVE :&str = "move to another box";
+    if i_choose(vec![TO_MARK, TO_MOVE], "what to do:") == TO_MARK {
+        for (bidx, tb) in boxes.iter_mut().enumerate() {
+            let ids :Vec<_> = selected.iter().filter(|h| h.bidx == bidx).map(|h| h.id).collect();
+            if ! ids.is_empty() { tb.mark(ids, false) }
+        }
+        println!("{}", S_success!("Tasks marked successfully!"));
+    } else {
+        let mut targets = vec!["today".to_string(), "tomorrow".to_string()];
+        for path in box_files(&basedir, false) {
+            let alias = get_box_alias(path.file_stem().unwrap().to_str().unwrap());
+            if ! targets.contains(&alias) { targets.push(alias) }
+        }
+        let target = i_choose(targets, "move to:");
+        let mut tb_to = TaskBox::new(get_inbox_file(&target));
+
+        for (bidx, tb) in boxes.iter_mut().enumerate() {
+            if tb.fpath == tb_to.fpath { continue }
+            let ids :Vec<_> = selected.iter().filter(|h| h.bidx == bidx).map(|h| h.id).collect();
+            if ids.is_empty() { continue }
+
+            tb.selected = Some(ids);
+            tb_to.collect_from(tb);
+        }
+    }
+}
+
+// the recent changes in journal, the latest first
+pub fn history(limit: usize) {
+    let entries = journal::history(Path::new(&Config_get!("basedir")));
+    if entries.is_empty() {
+        println!("{} changes recorded", S_empty!("no"));
+        return
+    }
+
+    for (n, entry) in entries.iter().take(limit).enumerate() {
+        println!("{:>3}  {}  {}  {} {}", n + 1, entry.time, S_fpath!(entry.command),
+                 S_checkbox!(TASKBOX), entry.boxes().join(", "));
+    }
+}
+
+// revert the latest N changes in journal
+pub fn undo(n: usize) {
+    match journal::undo(Path::new(&Config_get!("basedir")), n) {
+        Ok(entries) if entries.is_empty() => println!("{} to undo", S_empty!("nothing")),
+        Ok(entries) => for entry in entries {
+            println!("{} {}  {} {}", S_success!("undone:"), S_fpath!(entry.command),
+                     S_checkbox!(TASKBOX), entry.boxes().join(", "));
+        }
+        Err(e) => {
+            println!("{} {}", S_failure!("cannot undo:"), e);
+            journal::exit(1)
+        }
+    }
+}
+
 // clean up and all empty datetime taskbox and archive done tasks
 // rules:
 // 1. all empty boxed will be removed
 // 2. all boxes with only DONE tasks will be removed and the tasks go ARCHIVE box
-// 3. will keep "yesterday" "today" "tomorrow" untouched
+// 3. will keep "yesterday" "today" "tomorrow" untouched, and the weekly/monthly ones not ended before yesterday
 pub fn cleanup_and_archive() -> Result<()> {
     let basedir = Config_get!("basedir");
 
     let mut actions = Vec::new();
-    let yesterday = Local::now().date_naive() - Duration::days(1);
-    let re = Regex::new(r"(\d{4}-\d{2}-\d{2}).md$").unwrap();
-    for entry in std::fs::read_dir(&basedir)? {
-        let path = entry.expect("cannot get dir entry").path();
-        if path.is_file() {
-            if let Some(caps) = re.captures(path.to_str().unwrap()) {
-                let boxdate = NaiveDate::parse_from_str(&caps[1], "%Y-%m-%d").unwrap();
-                if boxdate < yesterday {
-                    let mut tb = TaskBox::new(path.clone());
-                    if tb.count() > 0 { continue }
-                    if tb.tasks.is_empty() {
-                        actions.push(("delete", String::from(path.file_stem().unwrap().to_str().unwrap()), path))
-                    } else {
-                        actions.push(("archive", String::from(path.file_stem().unwrap().to_str().unwrap()), path))
+    let yesterday = today_date() - Duration::days(1);
+    for (_, _, last, path) in period_boxes() {
+        // weekly and monthly boxes by their last dates
+        if last < yesterday {
+            let mut tb = TaskBox::new(path.clone());
+            if tb.count() > 0 { continue }
+            if tb.tasks.is_empty() {
+                actions.push(("delete", String::from(path.file_stem().unwrap().to_str().unwrap()), path))
+            } else {
+                actions.push(("archive", String::from(path.file_stem().unwrap().to_str().unwrap()), path))
 
-                    }
-                }
             }
         }
     }
@@ -190,7 +547,9 @@ pub fn cleanup_and_archive() -> Result<()> {
     if util::i_confirm("to apply?") {
         actions.into_iter().for_each(
             |(act, _name, path)| {
+                journal::track(&path);
                 if act == "archive" {
+                    journal::track(&archive_dir.join(path.file_name().unwrap()));
                     std::fs::rename(&path, archive_dir.join(path.file_name().unwrap())).expect("cannot move file");
                 } else {
                     std::fs::remove_file(&path).expect("cannot remove file");
@@ -1,5 +1,7 @@
 use clap::{Parser, Subcommand, ValueEnum};
+use chrono::Weekday;
 use crate::util;
+use crate::conf::RoutineCatchup;
 
 #[derive(Debug, Clone, Parser)]
 #[command(name= "todor")]
@@ -22,7 +24,7 @@ pub struct Cli {
     pub command: Option<Commands>,
 }
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Routine {
     Daily,
     Weekly,
@@ -30,6 +32,153 @@ pub enum Routine {
     Qweekly,
     Monthly,
     Once,
+    Yearly,
+    /// Mon to Fri
+    Weekdays,
+    EveryDays(u32),
+    /// e.g. Mon/Wed/Fri
+    OnWeekdays(Vec<Weekday>),
+    /// e.g. 2nd Tue of month
+    NthWeekday(u32, Weekday),
+    /// e.g. last Fri of month
+    LastWeekday(Weekday),
+}
+
+fn weekday_name(wd: &Weekday) -> String {
+    wd.to_string().to_lowercase()
+}
+
+impl Routine {
+    /// from cli input or the token in routine rule, e.g. "daily", "every 3 days",
+    /// "mon/wed/fri", "weekdays", "2nd tue", "last fri", "yearly"
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim().to_lowercase();
+        let words :Vec<&str> = input.split(|c: char| c.is_whitespace() || c == '-')
+                                    .filter(|w| !w.is_empty()).collect();
+
+        let routine = match words.as_slice() {
+            ["d"] | ["daily"] | ["every", "day"] => Routine::Daily,
+            ["w"] | ["weekly"] | ["every", "week"] => Routine::Weekly,
+            ["b"] | ["biweekly"] => Routine::Biweekly,
+            ["q"] | ["qweekly"] => Routine::Qweekly,
+            ["m"] | ["monthly"] | ["every", "month"] => Routine::Monthly,
+            ["1"] | ["once"] | ["reminder"] => Routine::Once,
+            ["y"] | ["yearly"] | ["annually"] | ["every", "year"] => Routine::Yearly,
+            ["wd"] | ["weekdays"] => Routine::Weekdays,
+            ["every", n, "days"] | ["every", n, "day"] =>
+                Routine::EveryDays(n.parse().map_err(|_| format!("invalid number: {}", n))?),
+            ["every", n, "weeks"] | ["every", n, "week"] =>
+                Routine::EveryDays(7 * n.parse::<u32>().map_err(|_| format!("invalid number: {}", n))?),
+            ["last", wd] => Routine::LastWeekday(wd.parse().map_err(|_| format!("invalid weekday: {}", wd))?),
+            [token] if token.starts_with("last") =>
+                Routine::LastWeekday(token[4..].parse().map_err(|_| format!("invalid weekday: {}", token))?),
+            [nth, wd] if nth.starts_with(|c: char| c.is_ascii_digit()) && wd.parse::<Weekday>().is_ok() => {
+                let n = nth.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse()
+                           .map_err(|_| format!("invalid number: {}", nth))?;
+                Routine::NthWeekday(n, wd.parse().unwrap())
+            }
+            [token] if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                let at = token.find(|c: char| !c.is_ascii_digit()).unwrap_or(token.len());
+                let n :u32 = token[..at].parse().map_err(|_| format!("invalid routine: {}", token))?;
+                match &token[at..] {
+                    "d" => Routine::EveryDays(n),
+                    "w" => Routine::EveryDays(7 * n),
+                    wd => Routine::NthWeekday(n, wd.parse().map_err(|_| format!("invalid routine: {}", token))?),
+                }
+            }
+            _ => {
+                let wds :Result<Vec<Weekday>, _> = input.split([',', '/', ' '])
+                                                        .filter(|w| !w.is_empty())
+                                                        .map(|w| w.parse()).collect();
+                match wds {
+                    Ok(wds) if !wds.is_empty() => Routine::OnWeekdays(wds),
+                    _ => return Err(format!("invalid routine: {}", input)),
+                }
+            }
+        };
+
+        match routine {
+            Routine::EveryDays(0) => Err("every 0 days is invalid".into()),
+            Routine::NthWeekday(n, _) if !(1..=5).contains(&n) => Err(format!("invalid nth: {}", n)),
+            routine => Ok(routine),
+        }
+    }
+
+    /// the token in routine rule, e.g. "d", "3d", "mon,wed,fri", "2tue", "lastfri"
+    pub fn token(&self) -> String {
+        match self {
+            Routine::Daily    => "d".into(),
+            Routine::Weekly   => "w".into(),
+            Routine::Biweekly => "b".into(),
+            Routine::Qweekly  => "q".into(),
+            Routine::Monthly  => "m".into(),
+            Routine::Once     => "1".into(),
+            Routine::Yearly   => "y".into(),
+            Routine::Weekdays => "wd".into(),
+            Routine::EveryDays(n) => format!("{}d", n),
+            Routine::OnWeekdays(wds) => wds.iter().map(weekday_name).collect::<Vec<_>>().join(","),
+            Routine::NthWeekday(n, wd) => format!("{}{}", n, weekday_name(wd)),
+            Routine::LastWeekday(wd) => format!("last{}", weekday_name(wd)),
+        }
+    }
+
+    /// the name in checked out tasks, e.g. "daily", "every-3-days", "mon/wed/fri"
+    pub fn name(&self) -> String {
+        match self {
+            Routine::Daily    => "daily".into(),
+            Routine::Weekly   => "weekly".into(),
+            Routine::Biweekly => "biweekly".into(),
+            Routine::Qweekly  => "qweekly".into(),
+            Routine::Monthly  => "monthly".into(),
+            Routine::Once     => "reminder".into(),
+            Routine::Yearly   => "yearly".into(),
+            Routine::Weekdays => "weekdays".into(),
+            Routine::EveryDays(n) => format!("every-{}-days", n),
+            Routine::OnWeekdays(wds) => wds.iter().map(weekday_name).collect::<Vec<_>>().join("/"),
+            Routine::NthWeekday(n, wd) => format!("{}{}-{}", n, match n {
+                1 => "st", 2 => "nd", 3 => "rd", _ => "th" }, weekday_name(wd)),
+            Routine::LastWeekday(wd) => format!("last-{}", weekday_name(wd)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Priority {
+    #[value(alias = "high")]
+    A,
+    #[value(alias = "medium")]
+    B,
+    #[value(alias = "low")]
+    C,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum MarkAs {
+    Done,
+    Cancelled,
+    InProgress,
+    Forwarded,
+    Scheduled,
+    Starred,
+}
+
+/// levels of date boxes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BoxLevel {
+    /// daily box, as "YYYY-MM-DD"
+    Day,
+    /// ISO week box, as "YYYY-Www"
+    Week,
+    /// month box, as "YYYY-MM"
+    Month,
+}
+
+/// output format of the read commands
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -43,12 +192,55 @@ pub enum Commands {
         #[arg(short = 'd', long)]
         date_stamp: bool,
 
-        #[arg(short, long, value_enum)]
+        /// daily, weekly, biweekly, qweekly, monthly, once, yearly, weekdays,
+        /// "every 3 days", "mon/wed/fri", "2nd tue", "last fri"
+        #[arg(short, long, value_parser = Routine::parse, value_name = "ROUTINE")]
         routine: Option<Routine>,
 
         /// non-interactive mode for routine tasks(using today)
         #[arg(short = 'n', long)]
         non_interactive: bool,
+
+        /// start date of routine task, e.g. "next mon", "+3d" (non-interactive)
+        #[arg(short, long, value_name = "DATE", requires = "routine")]
+        start: Option<String>,
+
+        /// add as sub-task of: NUM in `list --numbered`, /regex/ or text, (empty to choose)
+        #[arg(short, long, value_name = "TASK", conflicts_with = "routine")]
+        #[arg(num_args = 0..=1, default_missing_value = "")]
+        under: Option<String>,
+
+        /// append #tag to the task, can be used multiple times
+        #[arg(short, long, value_name = "TAG")]
+        tag: Vec<String>,
+
+        /// append @context to the task, can be used multiple times
+        #[arg(short, long, value_name = "CONTEXT")]
+        context: Vec<String>,
+
+        /// priority of the task, as "(A)" in text
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
+
+        /// due date of the task, as "due:YYYY-MM-DD" in text, e.g. "fri", "in 2 weeks"
+        #[arg(long, value_name = "DATE", conflicts_with = "routine")]
+        due: Option<String>,
+
+        /// hide the task until the date, as "t:YYYY-MM-DD" in text
+        #[arg(long, value_name = "DATE", conflicts_with = "routine")]
+        defer: Option<String>,
+
+        /// end date of routine task, no more checkouts after it
+        #[arg(long, value_name = "DATE", requires = "routine")]
+        until: Option<String>,
+
+        /// max count of checkouts of routine task
+        #[arg(long, value_name = "N", requires = "routine")]
+        max: Option<u32>,
+
+        /// how to checkout the missed ones of routine task, instead of config "routine_catchup"
+        #[arg(long, value_enum, requires = "routine")]
+        catchup: Option<RoutineCatchup>,
     },
 
     /// -> mark item as done
@@ -56,6 +248,26 @@ pub enum Commands {
     Mark {
         #[arg(short, long)]
         delete: bool,
+
+        /// mark as other status than "done"
+        #[arg(long = "as", value_enum, value_name = "STATUS")]
+        status: Option<MarkAs>,
+
+        /// non-interactive mode: the numbers shown by `list --numbered`
+        #[arg(value_name = "NUM")]
+        nums: Vec<usize>,
+
+        /// non-interactive mode: tasks matching the regex
+        #[arg(short = 'm', long = "match", value_name = "REGEX")]
+        pattern: Option<String>,
+
+        /// non-interactive mode: tasks with the exact text
+        #[arg(short, long)]
+        text: Option<String>,
+
+        /// non-interactive mode: all uncompeleted tasks
+        #[arg(short, long)]
+        all: bool,
     },
 
     /// -> list all uncompeleted tasks in box
@@ -68,11 +280,79 @@ pub enum Commands {
         /// plain mode means only show major task without styles
         #[arg(long)]
         plain: bool,
+
+        /// show numbers of tasks, which can be used by `mark <NUM>`
+        #[arg(short, long)]
+        numbered: bool,
+
+        /// only tasks with the #tag, can be used multiple times
+        #[arg(short, long, value_name = "TAG")]
+        tag: Vec<String>,
+
+        /// only tasks with the @context, can be used multiple times
+        #[arg(short, long, value_name = "CONTEXT")]
+        context: Vec<String>,
+
+        /// list the matched tasks in all boxes, with --tag/--context
+        #[arg(short = 'B', long)]
+        all_boxes: bool,
+
+        /// sort by priority (sub-tasks follow their parents) without changing the file
+        #[arg(short, long)]
+        sort: bool,
+
+        /// output format, see docs/json-output.md for json schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// -> show tasks due in the coming days, and the ones in daily boxes
+    #[clap(visible_aliases(["ag"]))]
+    Agenda {
+        /// how many days to show, including today
+        #[arg(short, long, default_value_t = 7)]
+        days: i64,
+    },
+
+    /// -> search tasks in all boxes
+    #[clap(visible_aliases(["s"]))]
+    Search {
+        /// regex, or chars in order with --fuzzy
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+
+        /// fuzzy matching instead of regex
+        #[arg(short, long)]
+        fuzzy: bool,
+
+        /// only uncompeleted tasks
+        #[arg(short, long, conflicts_with = "done")]
+        open: bool,
+
+        /// only compeleted tasks
+        #[arg(long)]
+        done: bool,
+
+        /// including boxes in archives
+        #[arg(short, long)]
+        archives: bool,
+
+        /// including encrypted boxes (password needed)
+        #[arg(short, long)]
+        encrypted: bool,
+
+        /// interactive mode to mark or move the hits
+        #[arg(short, long)]
+        interactive: bool,
     },
 
     /// -> list all(including compeleted) tasks
     #[clap(visible_aliases(["la"]))]
-    Listall,
+    Listall {
+        /// output format, see docs/json-output.md for json schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
 
     /// -> list all todo box in working dir
     #[clap(visible_aliases(["lb"]))]
@@ -80,6 +360,10 @@ pub enum Commands {
         /// show basedir only
         #[arg(long)]
         basedir_only: bool,
+
+        /// output format, see docs/json-output.md for json schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
     },
 
     /// -> encrypt todo box file
@@ -101,7 +385,11 @@ pub enum Commands {
 
     /// -> count items in inbox
     #[clap(visible_aliases(["c"]))]
-    Count,
+    Count {
+        /// output format, see docs/json-output.md for json schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
 
     /// -> show items in all inboxes
     #[clap(visible_aliases(["b"]))]
@@ -109,11 +397,26 @@ pub enum Commands {
 
     /// -> purge all the duplicated lines
     Purge {
+        /// sort by completed and priority, sub-tasks follow their parents
         #[arg(short, long)]
         sort: bool,
     }, // no alias for safe
 
-    /// -> sink all outdated uncompeleted to "today"
+    /// -> undo the last N changes of the boxes, see "history"
+    Undo {
+        #[arg(value_name = "N", default_value_t = 1)]
+        n: usize,
+    },
+
+    /// -> list the recent changes of the boxes, the latest first
+    #[clap(visible_aliases(["hist"]))]
+    History {
+        /// the number of changes to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// -> sink all outdated uncompeleted to "today"(and "thisweek", "thismonth")
     Sink {
         /// interactive mode to select items to move
         #[arg(short, long)]
@@ -122,6 +425,10 @@ pub enum Commands {
         /// whether to clean up old days boxex
         #[arg(short, long)]
         cleanup: bool,
+
+        /// sink all levels of outdated boxes into the current box of the level
+        #[arg(long, value_enum, value_name = "LEVEL")]
+        into: Option<BoxLevel>,
     },
 
     /// -> shift all uncompeleted in "today" to "tomorrow"
@@ -129,6 +436,14 @@ pub enum Commands {
         /// interactive mode to select items to move
         #[arg(short, long)]
         interactive: bool,
+
+        /// the box to shift from, e.g. "thisweek"
+        #[arg(long, value_name = "task-box-name", default_value = "today")]
+        from: String,
+
+        /// the box to shift to, e.g. "nextweek", "thismonth"
+        #[arg(long, value_name = "task-box-name", default_value = "tomorrow")]
+        to: String,
     },
 
     /// -> collect all uncompeleted in INBOX(or --from <box>) to "today"
@@ -140,6 +455,10 @@ pub enum Commands {
         /// interactive mode to select items to move
         #[arg(short, long)]
         interactive: bool,
+
+        /// only the tasks with the #tag, can be used multiple times
+        #[arg(short, long, value_name = "TAG", conflicts_with = "interactive")]
+        tag: Vec<String>,
     },
 
     /// -> pooling all uncompeleted of today to INBOX
@@ -147,6 +466,10 @@ pub enum Commands {
         /// interactive mode to select items to move
         #[arg(short, long)]
         interactive: bool,
+
+        /// only the tasks with the #tag, can be used multiple times
+        #[arg(short, long, value_name = "TAG", conflicts_with = "interactive")]
+        tag: Vec<String>,
     },
 
     /// -> import uncompeleted task in any markdown file to current
@@ -163,11 +486,80 @@ pub enum Commands {
     Filemanager,
 
     /// -> checkout routine tasks to "today"(collect --from routine)
-    Checkout,
+    Checkout {
+        /// checkout to the box of the date, e.g. "2026-10-23", "next fri"
+        #[arg(long, value_name = "DATE")]
+        date: Option<String>,
+
+        /// checkout to the boxes of the dates, e.g. "mon..fri", "today..+6d"
+        #[arg(long, value_name = "FROM..TO", conflicts_with = "date")]
+        range: Option<String>,
+    },
+
+    /// -> move tasks to the box of the date, e.g. "next mon", "2026-10-23"
+    #[clap(visible_alias("sch"))]
+    Schedule {
+        /// NUM in `list --numbered`, /regex/ or text
+        #[arg(value_name = "TASK")]
+        selector: String,
+
+        #[arg(value_name = "DATE")]
+        date: String,
+    },
 
     /// -> shortcut command to list all routine tasks
     #[clap(visible_aliases(["r", "rt"]))]
-    Routines,
+    Routines {
+        /// output format, see docs/json-output.md for json schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// show the occurrences in the coming days as a calendar
+        #[arg(short, long)]
+        upcoming: bool,
+
+        /// how many days to show, including today
+        #[arg(short, long, default_value_t = 30, requires = "upcoming")]
+        days: i64,
+
+        /// output the upcoming occurrences as iCalendar
+        #[arg(long, requires = "upcoming", conflicts_with = "format")]
+        ics: bool,
+    },
+
+    /// -> pause, resume or skip routine rules in ROUTINES box
+    Routine {
+        #[command(subcommand)]
+        action: RoutineAction,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum RoutineAction {
+    /// no more checkouts until resumed
+    Pause {
+        /// NUM in `-i routine list --numbered`, /regex/ or text
+        #[arg(value_name = "RULE")]
+        selector: String,
+
+        /// resume automatically after the date, e.g. "next mon", "in 2 weeks"
+        #[arg(long, value_name = "DATE")]
+        until: Option<String>,
+    },
+
+    /// checkout again as usual
+    Resume {
+        /// NUM in `-i routine list --numbered`, /regex/ or text
+        #[arg(value_name = "RULE")]
+        selector: String,
+    },
+
+    /// skip the next occurrence after today
+    SkipNext {
+        /// NUM in `-i routine list --numbered`, /regex/ or text
+        #[arg(value_name = "RULE")]
+        selector: String,
+    },
 }
 
 impl Default for Cli {
@@ -1,8 +1,10 @@
 use std::path::PathBuf;
 use std::sync::RwLock;
+use std::collections::HashMap;
 use dirs;
 use toml;
 use serde::Deserialize;
+use clap::ValueEnum;
 use lazy_static::lazy_static;
 
 use crate::util::*;
@@ -16,6 +18,38 @@ basedir = "~/.local/share/todor"
 
 ## blink the icons of items or not
 blink = true
+
+## the hour when a new day starts, e.g. 4 for the ones working past midnight,
+## then "today" is still the day before until 04:00
+#day_starts_at = 0
+
+## timezone for the dates, e.g. "Asia/Shanghai", the local one by default
+#timezone = "UTC"
+
+## how sub-tasks affect their parent task:
+##   "any": parent is done when any sub-task is done
+##   "all": parent is done when all sub-tasks are done
+##   "independent": no affects
+#subtask_policy = "any"
+
+## how to checkout the missed occurrences of routines since the last checkout,
+## can be overridden by "catchup:<how>" in routine rule:
+##   "collapse": one item of "overdue since" the first missed date
+##   "each": one item for each missed date
+##   "off": just ignore them
+#routine_catchup = "collapse"
+
+## how to coalesce the open instances of the same routine in one box,
+## e.g. yesterday's unfinished daily one sunk to today:
+##   "count": keep the newest one, annotated with "(missed N times)"
+##   "newest": keep the newest one only
+##   "off": keep all of them, e.g. to work with routine_catchup = "each"
+#routine_coalesce = "count"
+
+## settings for specified box
+#[boxes.INBOX]
+#subtask_policy = "all"
+#routine_coalesce = "off"
 "#;
 
 lazy_static! {
@@ -33,6 +67,40 @@ pub fn get_default_basedir() -> String {
         .to_string()
 }
 
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtaskPolicy {
+    #[default]
+    Any,
+    All,
+    Independent,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutineCatchup {
+    #[default]
+    Collapse,
+    Each,
+    Off,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RoutineCoalesce {
+    #[default]
+    Count,
+    Newest,
+    Off,
+}
+
+/// settings can be overridden per box
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct BoxConfig {
+    pub subtask_policy: Option<SubtaskPolicy>,
+    pub routine_coalesce: Option<RoutineCoalesce>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     /// base directory for todor data
@@ -40,6 +108,24 @@ pub struct Config {
 
     /// blink the icons of items or not
     pub blink: Option<bool>,
+
+    /// the hour(0-23) when a new day starts
+    pub day_starts_at: Option<u32>,
+
+    /// timezone name for the dates, e.g. "Asia/Shanghai", local timezone if None
+    pub timezone: Option<String>,
+
+    /// how sub-tasks affect their parent task
+    pub subtask_policy: Option<SubtaskPolicy>,
+
+    /// how to checkout the missed occurrences of routines
+    pub routine_catchup: Option<RoutineCatchup>,
+
+    /// how to coalesce the open instances of the same routine
+    pub routine_coalesce: Option<RoutineCoalesce>,
+
+    /// settings for specified boxes, by box name
+    pub boxes: Option<HashMap<String, BoxConfig>>,
 }
 
 impl Default for Config {
@@ -47,6 +133,12 @@ impl Default for Config {
         Config {
             basedir: Some(get_default_basedir()),
             blink: Some(true),
+            day_starts_at: Some(0),
+            timezone: None,
+            subtask_policy: Some(SubtaskPolicy::Any),
+            routine_catchup: Some(RoutineCatchup::Collapse),
+            routine_coalesce: Some(RoutineCoalesce::Count),
+            boxes: None,
         }
     }
 }
@@ -60,6 +152,46 @@ impl Config {
         if let Some(blink) = aconf.blink {
             self.blink = Some(blink);
         }
+
+        if let Some(hour) = aconf.day_starts_at {
+            self.day_starts_at = Some(hour);
+        }
+
+        if let Some(timezone) = &aconf.timezone {
+            self.timezone = Some(timezone.clone());
+        }
+
+        if let Some(policy) = aconf.subtask_policy {
+            self.subtask_policy = Some(policy);
+        }
+
+        if let Some(catchup) = aconf.routine_catchup {
+            self.routine_catchup = Some(catchup);
+        }
+
+        if let Some(coalesce) = aconf.routine_coalesce {
+            self.routine_coalesce = Some(coalesce);
+        }
+
+        if let Some(boxes) = &aconf.boxes {
+            self.boxes.get_or_insert_with(HashMap::new).extend(boxes.clone());
+        }
+    }
+
+    pub fn get_subtask_policy(&self, boxname: &str) -> SubtaskPolicy {
+        self.boxes.as_ref()
+            .and_then(|boxes| boxes.get(boxname))
+            .and_then(|boxconf| boxconf.subtask_policy)
+            .or(self.subtask_policy)
+            .unwrap_or_default()
+    }
+
+    pub fn get_routine_coalesce(&self, boxname: &str) -> RoutineCoalesce {
+        self.boxes.as_ref()
+            .and_then(|boxes| boxes.get(boxname))
+            .and_then(|boxconf| boxconf.routine_coalesce)
+            .or(self.routine_coalesce)
+            .unwrap_or_default()
     }
 
     pub fn load(path_str: Option<String>) -> Self {
@@ -94,6 +226,14 @@ impl Config {
         if let Some(basedir) = conf.basedir {
             conf.basedir = Some(util::path_normalize(&basedir))
         }
+        if conf.day_starts_at.is_some_and(|hour| hour > 23) {
+            eprintln!("invalid day_starts_at(0-23), ignore and use 0");
+            conf.day_starts_at = None
+        }
+        if conf.timezone.as_ref().is_some_and(|tz| tz.parse::<chrono_tz::Tz>().is_err()) {
+            eprintln!("unknown timezone, ignore and use the local one");
+            conf.timezone = None
+        }
 
         work_conf.update_with(&conf);
         work_conf
@@ -115,11 +255,25 @@ mod tests {
         let testtoml = temp_dir.path().join("config.toml");
         let testcontent = r#"basedir = "/tmp/.todor-test/"
         blink = false
+        subtask_policy = "all"
+        routine_catchup = "off"
+        routine_coalesce = "newest"
+        day_starts_at = 4
+        timezone = "Asia/Shanghai"
+
+        [boxes.INBOX]
+        subtask_policy = "independent"
         "#;
         std::fs::write(&testtoml, testcontent).expect("write err");
         let conf = Config::load(Some(testtoml.to_str().unwrap().into()));
         assert_eq!(conf.basedir, Some("/tmp/.todor-test/".into()));
         assert_eq!(conf.blink, Some(false));
+        assert_eq!(conf.get_subtask_policy("today"), SubtaskPolicy::All);
+        assert_eq!(conf.get_subtask_policy("INBOX"), SubtaskPolicy::Independent);
+        assert_eq!(conf.routine_catchup, Some(RoutineCatchup::Off));
+        assert_eq!(conf.routine_coalesce, Some(RoutineCoalesce::Newest));
+        assert_eq!(conf.day_starts_at, Some(4));
+        assert_eq!(conf.timezone, Some("Asia/Shanghai".into()));
     }
 
     #[test]
@@ -127,6 +281,7 @@ mod tests {
         let conf = Config::default();
         assert_eq!(conf.basedir, Some(get_default_basedir()));
         assert_eq!(conf.blink, Some(true));
+        assert_eq!(conf.get_subtask_policy("INBOX"), SubtaskPolicy::Any);
     }
 
     #[test]
@@ -135,9 +290,16 @@ mod tests {
         let aconf = Config {
             basedir: Some("/nowhere".into()),
             blink: Some(false),
+            day_starts_at: None,
+            timezone: None,
+            subtask_policy: None,
+            routine_catchup: Some(RoutineCatchup::Each),
+            routine_coalesce: None,
+            boxes: None,
         };
         conf.update_with(&aconf);
         assert_eq!(conf.basedir, Some("/nowhere".into()));
         assert_eq!(conf.blink, Some(false));
+        assert_eq!(conf.routine_catchup, Some(RoutineCatchup::Each));
     }
 }
@@ -0,0 +1,220 @@
+use std::fs;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use serde::{Serialize, Deserialize};
+
+use crate::util;
+
+// journal of mutating commands, in basedir as:
+//   .journal/<seq>/entry.json  -- time, command line and the touched files
+//   .journal/<seq>/<n>.before  -- the file content before the command, if existed
+//   .journal/<seq>/<n>.after   -- the file content after the command, if existed
+// the raw bytes are kept, so encrypted boxes stay encrypted in it
+pub const JOURNAL_DIR: &str = ".journal";
+const JOURNAL_MAX: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalFile {
+    pub path: PathBuf,
+    pub before: bool,
+    pub after: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[serde(skip)]
+    pub seq: u64,
+    pub time: String,
+    pub command: String,
+    pub files: Vec<JournalFile>,
+}
+
+impl JournalEntry {
+    /// the names of touched boxes
+    pub fn boxes(&self) -> Vec<String> {
+        self.files.iter()
+            .filter_map(|f| f.path.file_stem().and_then(|s| s.to_str()).map(String::from))
+            .collect()
+    }
+}
+
+struct Recording {
+    basedir: PathBuf,
+    command: String,
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+thread_local! {
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+}
+
+/// finish the recording when dropped, for any early return
+pub struct JournalGuard;
+
+impl Drop for JournalGuard {
+    fn drop(&mut self) {
+        if let Err(e) = finish() {
+            eprintln!("cannot write journal: {}", e)
+        }
+    }
+}
+
+/// start recording a command in current thread, till `finish()` or the guard dropped
+#[must_use]
+pub fn start(basedir: &Path, command: &str) -> JournalGuard {
+    RECORDING.set(Some(Recording {
+        basedir: basedir.to_path_buf(),
+        command: command.to_string(),
+        files: Vec::new(),
+    }));
+    JournalGuard
+}
+
+/// `std::process::exit()` with the recording finished, which skips the guard
+pub fn exit(code: i32) -> ! {
+    drop(JournalGuard);
+    std::process::exit(code)
+}
+
+/// keep the content of the file before it's changed, should be called before any writing
+pub fn track(path: &Path) {
+    RECORDING.with_borrow_mut(|rec| {
+        let Some(rec) = rec else { return };
+        let path = std::path::absolute(path).unwrap_or(path.to_path_buf());
+        if rec.files.iter().any(|(p, _)| *p == path) { return }
+        let before = fs::read(&path).ok();
+        rec.files.push((path, before));
+    })
+}
+
+/// stop recording and save the changed files as a new entry, if any
+pub fn finish() -> Result<Option<JournalEntry>> {
+    let Some(rec) = RECORDING.take() else { return Ok(None) };
+
+    let changes :Vec<_> = rec.files.into_iter()
+        .map(|(path, before)| { let after = fs::read(&path).ok(); (path, before, after) })
+        .filter(|(_, before, after)| before != after)
+        .collect();
+    if changes.is_empty() { return Ok(None) }
+
+    let jdir = rec.basedir.join(JOURNAL_DIR);
+    let seq = _seqs(&jdir).last().map_or(1, |s| s + 1);
+    let edir = jdir.join(format!("{:06}", seq));
+    fs::create_dir_all(&edir)?;
+
+    let mut files = Vec::new();
+    for (n, (path, before, after)) in changes.into_iter().enumerate() {
+        if let Some(before) = &before { fs::write(edir.join(format!("{}.before", n)), before)? }
+        if let Some(after) = &after { fs::write(edir.join(format!("{}.after", n)), after)? }
+        files.push(JournalFile { path, before: before.is_some(), after: after.is_some() });
+    }
+
+    let entry = JournalEntry {
+        seq,
+        time: util::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        command: rec.command,
+        files,
+    };
+    fs::write(edir.join("entry.json"), serde_json::to_string_pretty(&entry)?)?;
+
+    // only the latest ones are kept
+    let seqs = _seqs(&jdir);
+    for old in &seqs[..seqs.len().saturating_sub(JOURNAL_MAX)] {
+        fs::remove_dir_all(jdir.join(format!("{:06}", old)))?
+    }
+
+    Ok(Some(entry))
+}
+
+/// all entries in the journal, the latest first
+pub fn history(basedir: &Path) -> Vec<JournalEntry> {
+    let jdir = basedir.join(JOURNAL_DIR);
+    _seqs(&jdir).into_iter().rev()
+        .filter_map(|seq| {
+            let content = fs::read_to_string(jdir.join(format!("{:06}", seq)).join("entry.json")).ok()?;
+            let entry :JournalEntry = serde_json::from_str(&content).ok()?;
+            Some(JournalEntry { seq, ..entry })
+        })
+        .collect()
+}
+
+/// restore the files of the latest `n` entries to their contents before,
+/// refused if any of them was changed since
+pub fn undo(basedir: &Path, n: usize) -> Result<Vec<JournalEntry>> {
+    let jdir = basedir.join(JOURNAL_DIR);
+    let entries :Vec<_> = history(basedir).into_iter().take(n).collect();
+
+    // the files are expected as the "after" of the latest one touching them
+    let mut expected :Vec<(&Path, Option<PathBuf>)> = Vec::new();
+    for entry in &entries {
+        let edir = jdir.join(format!("{:06}", entry.seq));
+        for (i, file) in entry.files.iter().enumerate() {
+            if expected.iter().any(|(p, _)| *p == file.path) { continue }
+            expected.push((&file.path, file.after.then(|| edir.join(format!("{}.after", i)))));
+        }
+    }
+    for (path, after) in &expected {
+        let after = after.as_ref().map(fs::read).transpose()?;
+        if fs::read(path).ok() != after {
+            return Err(anyhow!("{} was changed since", path.display()))
+        }
+    }
+
+    for entry in &entries {
+        let edir = jdir.join(format!("{:06}", entry.seq));
+        for (i, file) in entry.files.iter().enumerate() {
+            if file.before {
+                if let Some(parent) = file.path.parent() { fs::create_dir_all(parent)? }
+                fs::write(&file.path, fs::read(edir.join(format!("{}.before", i)))?)?;
+            } else if file.path.exists() {
+                fs::remove_file(&file.path)?;
+            }
+        }
+        fs::remove_dir_all(&edir)?;
+    }
+
+    Ok(entries)
+}
+
+/// drop the file from all entries, e.g. the plain box before encrypted
+pub fn forget(basedir: &Path, path: &Path) -> Result<()> {
+    let jdir = basedir.join(JOURNAL_DIR);
+    let path = std::path::absolute(path)?;
+
+    for mut entry in history(basedir) {
+        let Some(i) = entry.files.iter().position(|f| f.path == path) else { continue };
+        let edir = jdir.join(format!("{:06}", entry.seq));
+
+        // the snapshot files are renamed to keep the numbering
+        let files = std::mem::take(&mut entry.files);
+        for (n, file) in files.into_iter().enumerate() {
+            for stage in ["before", "after"] {
+                let snap = edir.join(format!("{}.{}", n, stage));
+                if n == i {
+                    if snap.exists() { fs::remove_file(&snap)? }
+                } else if n > i && snap.exists() {
+                    fs::rename(&snap, edir.join(format!("{}.{}", n - 1, stage)))?
+                }
+            }
+            if n != i { entry.files.push(file) }
+        }
+
+        if entry.files.is_empty() {
+            fs::remove_dir_all(&edir)?
+        } else {
+            fs::write(edir.join("entry.json"), serde_json::to_string_pretty(&entry)?)?
+        }
+    }
+
+    Ok(())
+}
+
+// sequence numbers of the entries, ascending
+fn _seqs(jdir: &Path) -> Vec<u64> {
+    let mut seqs :Vec<u64> = fs::read_dir(jdir).into_iter().flatten()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    seqs.sort();
+    seqs
+}
@@ -1,6 +1,5 @@
 use std::path;
 use colored::Colorize;
-use chrono::*;
 use regex::Regex;
 
 use todor::taskbox::*;
@@ -10,6 +9,7 @@ use todor::util::*;
 
 use todor::util;
 use todor::boxops;
+use todor::journal;
 
 fn main() {
     let args = Cli::default();
@@ -41,20 +41,52 @@ fn main() {
 
     let mut inbox_path = util::get_inbox_file(inbox);
 
+    // the changes of boxes are recorded for "undo", except the journal ones and enc/dec
+    let _journal = if ! matches!(args.command, Some(Commands::Undo { .. } | Commands::History { .. } | Commands::Enc | Commands::Dec)) {
+        let cmdline :Vec<_> = std::env::args().skip(1)
+            .map(|arg| if arg.contains(char::is_whitespace) { format!("{:?}", arg) } else { arg })
+            .collect();
+        let cmdname = arg0.split(path::MAIN_SEPARATOR).next_back().unwrap();
+        Some(journal::start(path::Path::new(&Config_get!("basedir")), format!("{} {}", cmdname, cmdline.join(" ")).trim_end()))
+    } else { None };
+
     match args.command {
-        Some(Commands::Listall)     => TaskBox::new(inbox_path).list(true, false),
         Some(Commands::Enc)         => TaskBox::new(inbox_path).encrypt().unwrap(),
         Some(Commands::Dec)         => TaskBox::new(inbox_path).decrypt().unwrap(),
-        Some(Commands::Routines)    => TaskBox::new(get_inbox_file(ROUTINE_BOXNAME)).list(true, false),
-        None                        => TaskBox::new(inbox_path).list(false, false),
+        None                        => TaskBox::new(inbox_path).list(false, false, false, &TaskFilter::default(), false),
+
+        Some(Commands::Listall { format }) => match format {
+            OutputFormat::Text => TaskBox::new(inbox_path).list(true, false, false, &TaskFilter::default(), false),
+            _ => TaskBox::new(inbox_path).list_json(true, format, &TaskFilter::default()),
+        }
+
+        Some(Commands::Routines { upcoming: true, days, format, ics }) => boxops::upcoming_routines(days, format, ics),
+        Some(Commands::Routines { format, .. }) => {
+            let mut tb = TaskBox::new(get_inbox_file(ROUTINE_BOXNAME));
+            match format {
+                OutputFormat::Text => tb.list(true, false, false, &TaskFilter::default(), false),
+                _ => tb.list_json(true, format, &TaskFilter::default()),
+            }
+        }
 
-        Some(Commands::List{ all, plain }) => {
-            TaskBox::new(inbox_path).list(all, plain)
+        Some(Commands::List{ all, plain, numbered, format, tag, context, all_boxes, sort }) => {
+            let filter = TaskFilter::new(&tag, &context);
+            if all_boxes {
+                boxops::list_matched_boxes(all, format, &filter, sort)
+            } else {
+                match format {
+                    OutputFormat::Text => TaskBox::new(inbox_path).list(all, plain, numbered, &filter, sort),
+                    _ => TaskBox::new(inbox_path).list_json(all, format, &filter),
+                }
+            }
         }
 
-        Some(Commands::Count)             => {
-            let cc = TaskBox::new(inbox_path).count();
-            if cc > 0 { println!("{}", cc) }
+        Some(Commands::Count { format }) => match format {
+            OutputFormat::Text => {
+                let cc = TaskBox::new(inbox_path).count();
+                if cc > 0 { println!("{}", cc) }
+            }
+            _ => TaskBox::new(inbox_path).count_json(format),
         }
 
         Some(Commands::Import{ file, from_logseq }) => {
@@ -63,44 +95,73 @@ fn main() {
 
         Some(Commands::Purge { sort }) => {
             if i_confirm("are you sure?") {
-                if sort && ! i_confirm("sort cannot handle subtasks well, continue?") { return }
                 TaskBox::new(inbox_path).purge(sort)
             }
         }
 
-        Some(Commands::Checkout) => { // ROUTINE --(check-out)-> today/tomorrow
+        Some(Commands::Checkout { date, range }) => { // ROUTINE --(check-out)-> date boxes
             let real_inbox = if inbox != "tomorrow" { "today" } else { inbox };
-            TaskBox::new(util::get_inbox_file(real_inbox))
-                  .collect_from(&mut TaskBox::new(util::get_inbox_file("routine")))
+            let dates = if let Some(range) = range {
+                util::parse_date_range(&range).unwrap_or_else(|| {
+                    println!("{} {}", S_failure!("invalid date range:"), range);
+                    journal::exit(1)
+                })
+            } else {
+                let date = date.unwrap_or(real_inbox.into());
+                vec![parse_date(&date).unwrap_or_else(|| {
+                    println!("{} {}", S_failure!("invalid date:"), date);
+                    journal::exit(1)
+                })]
+            };
+
+            let mut tb_routine = TaskBox::new(util::get_inbox_file("routine"));
+            for date in dates {
+                let mut tb_to = TaskBox::new(util::get_inbox_file(&date));
+                tb_to.collect_from(&mut tb_routine);
+
+                // INBOX/named boxes --(tickler)-> today
+                if date == get_today() { tb_to.collect_ticklers() }
+            }
         }
 
-        Some(Commands::Sink { interactive, cleanup }) => { // outdated -> today
-            let mut boxes = Vec::new();
-            let re_date_box = Regex::new(r"\d{4}-\d{2}-\d{2}.md$").unwrap();
-            for entry in std::fs::read_dir(Config_get!("basedir")).expect("cannot read dir") {
-                let path = entry.expect("cannot get entry").path();
-                if path.is_file() && re_date_box.is_match(path.to_str().unwrap()) { 
-                    boxes.push(path)
-                }
+        Some(Commands::Schedule { selector, date }) => { // INBOX/other -> date box
+            let Some(date) = parse_date(&date) else {
+                println!("{} {}", S_failure!("invalid date:"), date);
+                journal::exit(1)
+            };
+            let mut tb_from = TaskBox::new(inbox_path);
+            if tb_from.tbname == ROUTINE_BOXNAME || tb_from.tbname == date {
+                println!("{} is not a valid source", S_movefrom!(tb_from.tbname));
+                journal::exit(1)
             }
-            boxes.sort(); boxes.reverse();
 
-            let today =  Local::now().date_naive();
-            let mut tb_today = TaskBox::new(util::get_inbox_file("today"));
-            for taskbox in boxes {
-                let boxdate = NaiveDate::parse_from_str(
-                    taskbox.file_stem().unwrap().to_str().unwrap(),
-                    "%Y-%m-%d").expect("something wrong!");
+            let selector = TaskSelector::parse(&selector).unwrap_or_else(|_| {
+                println!("{} {}", S_failure!("invalid regex:"), selector);
+                journal::exit(1)
+            });
+            let ids = tb_from.select(&selector);
+            if ids.is_empty() {
+                println!(" {} matched!", S_empty!("nothing"));
+                journal::exit(1)
+            }
+            tb_from.selected = Some(ids);
+            TaskBox::new(util::get_inbox_file(&date)).collect_from(&mut tb_from)
+        }
 
-                if boxdate < today {
+        Some(Commands::Sink { interactive, cleanup, into }) => { // outdated -> today/thisweek/thismonth
+            let today =  today_date();
+            for (level, _, last, taskbox) in boxops::period_boxes() {
+                if last < today {
                     let mut tb_from = TaskBox::new(taskbox);
                     if tb_from.count() == 0 { continue }
 
                     if interactive {
                         tb_from.selected = Some(i_select(tb_from.get_all_to_mark(),
-                                                &format!("choose from {}", boxdate)));
+                                                &format!("choose from {}", tb_from.tbname))
+                                                .iter().map(|t| t.id).collect());
                     }
-                    tb_today.collect_from(&mut tb_from);
+                    let to = period_box_name(into.unwrap_or(level), today);
+                    TaskBox::new(util::get_inbox_file(&to)).collect_from(&mut tb_from);
                     println!();
                 }
             }
@@ -111,24 +172,41 @@ fn main() {
             }
         }
 
-        Some(Commands::Shift { interactive }) => { // today -> tomorrow
-            let mut tb_today = TaskBox::new(util::get_inbox_file("today"));
+        Some(Commands::Shift { interactive, from, to }) => { // today -> tomorrow, or other date boxes
+            let mut tb_from = TaskBox::new(util::get_inbox_file(&from));
+            let mut tb_to = TaskBox::new(util::get_inbox_file(&to));
+            for tb in [&tb_from, &tb_to] {
+                if box_period(&tb.tbname).is_none() {
+                    println!("{} is not a date box", S_moveto!(tb.tbname));
+                    journal::exit(1)
+                }
+            }
+            if tb_from.fpath == tb_to.fpath {
+                println!("{} is not a valid target", S_moveto!(to));
+                journal::exit(1)
+            }
+
             if interactive {
-                tb_today.selected = Some(i_select(tb_today.get_all_to_mark(), "choose from TODAY"));
+                tb_from.selected = Some(i_select(tb_from.get_all_to_mark(),
+                                        &format!("choose from {}", from.to_uppercase()))
+                                        .iter().map(|t| t.id).collect());
             }
-            TaskBox::new(util::get_inbox_file("tomorrow")).collect_from(&mut tb_today)
+            tb_to.collect_from(&mut tb_from)
         }
 
-        Some(Commands::Pool { interactive }) => { // today -> INBOX
+        Some(Commands::Pool { interactive, tag }) => { // today -> INBOX
             let mut tb_today = TaskBox::new(util::get_inbox_file("today"));
             if interactive {
-                tb_today.selected = Some(i_select(tb_today.get_all_to_mark(), "choose from TODAY"));
+                tb_today.selected = Some(i_select(tb_today.get_all_to_mark(), "choose from TODAY")
+                                         .iter().map(|t| t.id).collect());
+            } else if ! tag.is_empty() {
+                tb_today.selected = Some(tb_today.select(&TaskSelector::Filter(TaskFilter::new(&tag, &[]))));
             }
 
             TaskBox::new(util::get_inbox_file("inbox")).collect_from(&mut tb_today)
         }
 
-        Some(Commands::Collect { from, interactive }) => { // other(def: INBOX) -> today
+        Some(Commands::Collect { from, interactive, tag }) => { // other(def: INBOX) -> today
             let from = from.unwrap_or("inbox".into());
             if from == get_today() || from == "today" {
                 println!("{} is not a valid source", S_moveto!("today"));
@@ -139,57 +217,201 @@ fn main() {
 
             if interactive {
                 tb_from.selected = Some(i_select(tb_from.get_all_to_mark(),
-                                                 &format!("choose from {}", from)));
+                                                 &format!("choose from {}", from))
+                                                 .iter().map(|t| t.id).collect());
+            } else if ! tag.is_empty() {
+                tb_from.selected = Some(tb_from.select(&TaskSelector::Filter(TaskFilter::new(&tag, &[]))));
             }
 
             TaskBox::new(util::get_inbox_file("today")).collect_from(&mut tb_from)
         }
 
-        Some(Commands::Mark { delete } ) => {
+        Some(Commands::Mark { delete, status, nums, pattern, text, all } ) => {
             let mut todo = TaskBox::new(inbox_path);
+            let status = status.map_or(TaskStatus::Done, TaskStatus::from);
+
+            let mut selectors :Vec<_> = nums.into_iter().map(TaskSelector::Index).collect();
+            if let Some(text) = text { selectors.push(TaskSelector::Text(text)) }
+            if let Some(pattern) = pattern {
+                selectors.push(TaskSelector::Match(Regex::new(&pattern).unwrap_or_else(|_| {
+                    println!("{} {}", S_failure!("invalid regex:"), pattern);
+                    journal::exit(1)
+                })))
+            }
+            if all { selectors.push(TaskSelector::All) }
+
+            if ! selectors.is_empty() {
+                // non-interactive mode
+                let mut ids = Vec::new();
+                for selector in &selectors {
+                    ids.extend(todo.select(selector))
+                }
+                if ids.is_empty() {
+                    println!(" {} matched!", S_empty!("nothing"));
+                    journal::exit(1)
+                }
+                todo.mark_as(ids, status, delete);
+                return
+            }
+
             let tasks = todo.get_all_to_mark();
             if tasks.is_empty() {
                 println!(" {} left!", S_empty!("nothing"));
                 return
             }
 
-            todo.mark(i_select(tasks, "choose to close:"), delete);
+            todo.mark_as(i_select(tasks, "choose to close:").iter().map(|t| t.id).collect(),
+                         status, delete);
         }
 
-        Some(Commands::Add { what, date_stamp, routine, non_interactive }) => {
+        Some(Commands::Add { what, date_stamp, routine, non_interactive, start, under, tag, context, priority, due, defer, until, max, catchup }) => {
             if routine.is_some() {
                 inbox_path = get_inbox_file("routine")
             }
             let mut todo = TaskBox::new(inbox_path);
             todo.load();
 
+            // parent tasks for sub-task
+            let parents = under.map(|under| {
+                let ids :Vec<_> = if under.is_empty() {
+                    let items :Vec<_> = todo.get_all_to_mark().into_iter()
+                                            .filter(|item| !item.warn).collect();
+                    i_select(items, "choose the parent task:").iter().map(|t| t.id).collect()
+                } else {
+                    let selector = TaskSelector::parse(&under).unwrap_or_else(|_| {
+                        println!("{} {}", S_failure!("invalid regex:"), under);
+                        journal::exit(1)
+                    });
+                    todo.select(&selector)
+                };
+
+                if ids.is_empty() {
+                    println!(" {} matched!", S_empty!("nothing"));
+                    journal::exit(1)
+                }
+                ids
+            });
+
             #[allow(clippy::redundant_closure)]
             let input = what.unwrap_or_else(|| i_gettext());
             if ! input.is_empty() {
+                let mut input = TaskFilter::new(&tag, &context).append_to(&input);
+                if let Some(due) = &due {
+                    input = with_due(&input, &parse_date(due).unwrap_or_else(|| {
+                        println!("{} {}", S_failure!("invalid date:"), due);
+                        journal::exit(1)
+                    }))
+                }
+                if let Some(defer) = &defer {
+                    input = with_tickler(&input, &parse_date(defer).unwrap_or_else(|| {
+                        println!("{} {}", S_failure!("invalid date:"), defer);
+                        journal::exit(1)
+                    }))
+                }
+                if let Some(priority) = priority {
+                    input = with_priority(&input, match priority {
+                        Priority::A => 'A',
+                        Priority::B => 'B',
+                        Priority::C => 'C',
+                    })
+                }
                 let mut start_date = get_today();
 
-                if routine.is_some() && !non_interactive {
-                    start_date = i_getdate(match routine {
-                            Some(Routine::Daily)    => "daily",
-                            Some(Routine::Weekly)   => "weekly",
-                            Some(Routine::Biweekly) => "biweekly",
-                            Some(Routine::Qweekly)  => "qweekly",
-                            Some(Routine::Monthly)  => "monthly",
-                            _ => "",
-                            })
+                if let Some(start) = &start {
+                    start_date = parse_date(start).unwrap_or_else(|| {
+                        println!("{} {}", S_failure!("invalid date:"), start);
+                        journal::exit(1)
+                    })
+                } else if let (Some(routine), false) = (&routine, non_interactive) {
+                    start_date = i_getdate(&routine.name())
                 }
 
-                todo.add(input, routine, date_stamp, &start_date);
+                let opts = RoutineOpts {
+                    until: until.map(|until| parse_date(&until).unwrap_or_else(|| {
+                        println!("{} {}", S_failure!("invalid date:"), until);
+                        journal::exit(1)
+                    })),
+                    times: max.map(|max| (0, max)),
+                    catchup,
+                    ..Default::default()
+                };
+
+                if let Some(parents) = parents {
+                    todo.add_sub(input, &parents, date_stamp);
+                } else if let (Some(routine), false) = (routine.clone(), opts == RoutineOpts::default()) {
+                    todo.add_routine(input, routine, &start_date, &opts);
+                } else {
+                    todo.add(input, routine, date_stamp, &start_date);
+                }
                 println!("{}", S_success!("Task added successfully!"));
             } else {
                 println!("{}", S_empty!("Empty input, skip."));
             }
         }
 
+        Some(Commands::Routine { action }) => {
+            let mut tb = TaskBox::new(get_inbox_file(ROUTINE_BOXNAME));
+            let (RoutineAction::Pause { selector, .. } |
+                 RoutineAction::Resume { selector } |
+                 RoutineAction::SkipNext { selector }) = &action;
+            let selector = TaskSelector::parse(selector).unwrap_or_else(|_| {
+                println!("{} {}", S_failure!("invalid regex:"), selector);
+                journal::exit(1)
+            });
+            let ids = tb.select(&selector);
+
+            let count = match action {
+                RoutineAction::Pause { until, .. } => {
+                    let until = until.map(|until| parse_date(&until).unwrap_or_else(|| {
+                        println!("{} {}", S_failure!("invalid date:"), until);
+                        journal::exit(1)
+                    }));
+                    tb.update_routines(&ids, |_, _, opts| {
+                        opts.paused = true;
+                        opts.paused_until = until.clone();
+                    })
+                }
+                RoutineAction::Resume { .. } => tb.update_routines(&ids, |_, _, opts| {
+                    // the ones in pausing are not missed
+                    if opts.paused && opts.last.is_some() {
+                        opts.last = opts.last.take().max(Some(get_yesterday()));
+                    }
+                    opts.paused = false;
+                    opts.paused_until = None;
+                }),
+                RoutineAction::SkipNext { .. } => tb.update_routines(&ids, |kind, start_date, opts| {
+                    // today's counts too, if not checked out yet
+                    let after = if opts.last.as_ref().is_some_and(|last| *last >= get_today()) {
+                        today_date()
+                    } else {
+                        today_date() - chrono::Duration::days(1)
+                    };
+                    opts.skip = util::next_routine_date(kind, start_date, after)
+                                    .map(|date| date.to_string());
+                }),
+            };
+
+            if count == 0 {
+                println!(" {} matched!", S_empty!("nothing"));
+                journal::exit(1)
+            }
+            println!("{}", S_success!(format!("{} routine(s) updated", count)));
+        }
+
+        Some(Commands::Search { pattern, fuzzy, open, done, archives, encrypted, interactive }) => {
+            let done = if open { Some(false) } else if done { Some(true) } else { None };
+            boxops::search(&pattern, fuzzy, done, archives, encrypted, interactive)
+        }
+
+        Some(Commands::Agenda { days }) => boxops::agenda(days),
+
         Some(Commands::Browse)      => boxops::browse().unwrap(),
         Some(Commands::Filemanager) => boxops::file_manager().unwrap(),
-        Some(Commands::Listbox { basedir_only })  => boxops::list_boxes(basedir_only),
+        Some(Commands::Listbox { basedir_only, format })  => boxops::list_boxes(basedir_only, format),
         Some(Commands::Edit { diffwith, routines }) =>
             boxops::edit_box(if routines { ROUTINE_BOXNAME } else { inbox }, diffwith),
+
+        Some(Commands::Undo { n }) => boxops::undo(n),
+        Some(Commands::History { limit }) => boxops::history(limit),
     }
 }
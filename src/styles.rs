@@ -20,6 +20,11 @@ pub const DATESTAMP: &str = "󰴹"; // 󰃵
 pub const DATESTAMP_PLAIN: &str = "@";
 pub const WEEKLINE: &str = "󰕶";
 pub const LOCKED: &str = "󰍁";
+pub const INPROGRESS: &str = "󰦖";
+pub const CANCELLED: &str = "󰅘";
+pub const FORWARDED: &str = "󰁕";
+pub const SCHEDULED: &str = "󰃰";
+pub const STARRED: &str = "󰓎";
 // S means Style
 #[macro_export]
 macro_rules! S_fpath { ($e:expr) => { $e.to_string().purple() }; }
@@ -1,45 +1,582 @@
 use std::fs;
+use std::fmt;
 use std::path::{Path, PathBuf};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{Read, Write};
 use regex::Regex;
 use colored::Colorize;
 use lazy_static::lazy_static;
 use zip::*;
 use anyhow::Result;
+use serde::Serialize;
+use chrono::NaiveDate;
+use clap::ValueEnum;
 
 use crate::cli::*;
 use crate::util::*;
 use crate::styles::*;
 use crate::conf::*;
+use crate::journal;
 
 lazy_static! {
     static ref RE_PREFIX_OPEN :Regex = Regex::new(r"^- \[[ ]\] (.*)").unwrap();
-    static ref RE_PREFIX_DONE :Regex = Regex::new(r"^- \[[xX\-/<>\*]\] (.*)").unwrap();
+    static ref RE_PREFIX_DONE :Regex = Regex::new(r"^- \[([xX\-/<>\*])\] (.*)").unwrap();
     static ref RE_ROUTINES :Regex =
-        Regex::new(r"\{󰃯:([dDwWbBqQmM1]) (\d{4}-\d{2}-\d{2})\w{3} 󰳟\} (.*)").unwrap();
+        Regex::new(r"\{󰃯:([\w,\-]+) (\d{4}-\d{2}-\d{2})\w{3}((?: [\w:/\-]+)*) 󰳟\} (.*)").unwrap();
     static ref RE_ROUTINES_CHECKOUT :Regex =
-        Regex::new(r"\{󰃯:(daily|weekly|biweekly|qweekly|monthly|reminder)\} (.*)").unwrap();
+        Regex::new(r"\{󰃯:([\w/\-]+)\} (.*)").unwrap();
+    static ref RE_DATESTAMP :Regex = Regex::new(r"\[󰴹 (\d{4}-\d{2}-\d{2})\]").unwrap();
+    static ref RE_TAGS :Regex = Regex::new(r"(?:^|\s)#([\w\-/]+)").unwrap();
+    static ref RE_CONTEXTS :Regex = Regex::new(r"(?:^|\s)@([\w\-/]+)").unwrap();
+    static ref RE_TAGS_CONTEXTS :Regex = Regex::new(r"(^|\s)([#@][\w\-/]+)").unwrap();
+    static ref RE_TICKLER :Regex = Regex::new(r"(^|\s)t:(\d{4}-\d{2}-\d{2})").unwrap();
+    static ref RE_DUE :Regex = Regex::new(r"(^|\s)due:(\d{4}-\d{2}-\d{2})").unwrap();
+    static ref RE_OVERDUE :Regex = Regex::new(r" \(overdue since \d{4}-\d{2}-\d{2}\)").unwrap();
+    static ref RE_MISSED :Regex = Regex::new(r" \(missed (\d+) times?\)").unwrap();
+    static ref RE_PRIORITY :Regex = Regex::new(r"(^|\s)(\([A-Z]\)|!{1,3})(\s|$)").unwrap();
 }
 
 pub const INBOX_BOXNAME :&str  = "INBOX";
 pub const ROUTINE_BOXNAME :&str  = "ROUTINES";
 
+/// version of json/ndjson output, see docs/json-output.md
+pub const JSON_SCHEMA_VERSION :u32 = 1;
+
 const PREFIX_OPEN :&str  = "- [ ] ";
-const PREFIX_DONE :&str  = "- [x] ";
 const PREFIX_SUBT :&str  = " 󱞩 ";
 
 const PREFIX_OPEN_LOGSEQ :&str  = "- TODO ";
 const PREFIX_OPEN2_LOGSEQ :&str  = "- LATER ";
 // const PREFIX_DONE_LOGSEQ :&str  = "- DONE ";
 
+// width of leading whitespaces, tab as 4 spaces
+fn indent_width(indent: &str) -> usize {
+    indent.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+// full name of routine kind in rules, e.g. "d" -> "daily"
+fn routine_kind_name(kind: &str) -> String {
+    Routine::parse(kind).map_or("unknown".into(), |r| r.name())
+}
+
+// colorize #tags, @contexts, priority and due date in task text
+fn highlight_tokens(text: &str) -> String {
+    let today = get_today();
+    let text = RE_DUE.replace_all(text, |caps: &regex::Captures| {
+        let token = format!("due:{}", &caps[2]);
+        let token = match caps[2].cmp(&today) {
+            std::cmp::Ordering::Less => token.red().bold(),
+            std::cmp::Ordering::Equal => token.yellow().bold(),
+            std::cmp::Ordering::Greater => token.normal(),
+        };
+        format!("{}{}", &caps[1], token)
+    });
+    let text = RE_TAGS_CONTEXTS.replace_all(&text, |caps: &regex::Captures| {
+        let token = if caps[2].starts_with('#') { caps[2].cyan() } else { caps[2].magenta() };
+        format!("{}{}", &caps[1], token)
+    });
+    RE_PRIORITY.replace_all(&text, |caps: &regex::Captures| {
+        let token = match parse_priority(&caps[2]) {
+            'A' => caps[2].red().bold(),
+            'B' => caps[2].yellow().bold(),
+            _ => caps[2].blue().bold(),
+        };
+        format!("{}{}{}", &caps[1], token, &caps[3])
+    }).to_string()
+}
+
+// "(A)" -> 'A', and "!!!" -> 'A', "!!" -> 'B', "!" -> 'C'
+fn parse_priority(marker: &str) -> char {
+    match marker {
+        "!!!" => 'A',
+        "!!" => 'B',
+        "!" => 'C',
+        _ => marker.chars().nth(1).unwrap_or('C'),
+    }
+}
+
+/// set the due date token of text, the existing one will be replaced
+pub fn with_due(text: &str, date: &str) -> String {
+    let text = RE_DUE.replace(text, "");
+    format!("{} due:{}", text.trim_end(), date)
+}
+
+/// set the "not before" date token of text, the existing one will be replaced
+pub fn with_tickler(text: &str, date: &str) -> String {
+    let text = RE_TICKLER.replace(text, "");
+    format!("{} t:{}", text.trim_end(), date)
+}
+
+/// set the priority marker of text as "(A) ...", the existing one will be replaced
+pub fn with_priority(text: &str, priority: char) -> String {
+    let text = RE_PRIORITY.replace(text, |caps: &regex::Captures| {
+        if caps[1].is_empty() || caps[3].is_empty() { String::new() } else { " ".to_string() }
+    });
+    format!("({}) {}", priority, text.trim_start())
+}
+
+// the same routine instances share it, without date stamp and annotations
+fn routine_instance_key(text: &str) -> String {
+    let text = RE_DATESTAMP.replace_all(text, "");
+    let text = RE_OVERDUE.replace_all(&text, "");
+    RE_MISSED.replace_all(&text, "").trim_end().to_string()
+}
+
+fn missed_times(text: &str) -> usize {
+    RE_MISSED.captures(text).map_or(0, |caps| caps[1].parse().unwrap_or(0))
+}
+
+// set "(missed N times)" of routine instance text, before the date stamp
+fn with_missed(text: &str, times: usize) -> String {
+    let text = RE_MISSED.replace(text, "");
+    let (text, stamp) = match RE_DATESTAMP.find(&text) {
+        Some(m) => (text[..m.start()].trim_end(), &text[m.start()..]),
+        None => (text.trim_end(), ""),
+    };
+    format!("{} (missed {} time{}) {}", text, times, if times > 1 { "s" } else { "" }, stamp)
+        .trim_end().to_string()
+}
+
+// parse one markdown line into (indent, status, text) if it's a task
+fn parse_task_line(line: &str) -> Option<(String, TaskStatus, String)> {
+    let stripped = line.trim_start();
+    let indent = line[..line.len() - stripped.len()].to_string();
+
+    if let Some(caps) = RE_PREFIX_OPEN.captures(stripped) {
+        Some((indent, TaskStatus::Open, caps[1].to_string()))
+    } else {
+        RE_PREFIX_DONE.captures(stripped)
+            .map(|caps| (indent, TaskStatus::from_char(&caps[1]), caps[2].to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    Open,       // [ ]
+    Done,       // [x]
+    Cancelled,  // [-]
+    InProgress, // [/]
+    Forwarded,  // [>]
+    Scheduled,  // [<]
+    Starred,    // [*]
+}
+
+impl TaskStatus {
+    pub fn from_char(c: &str) -> Self {
+        match c {
+            "x" | "X" => TaskStatus::Done,
+            "-" => TaskStatus::Cancelled,
+            "/" => TaskStatus::InProgress,
+            ">" => TaskStatus::Forwarded,
+            "<" => TaskStatus::Scheduled,
+            "*" => TaskStatus::Starred,
+            _ => TaskStatus::Open,
+        }
+    }
+
+    pub fn to_char(&self) -> &str {
+        match self {
+            TaskStatus::Open       => " ",
+            TaskStatus::Done       => "x",
+            TaskStatus::Cancelled  => "-",
+            TaskStatus::InProgress => "/",
+            TaskStatus::Forwarded  => ">",
+            TaskStatus::Scheduled  => "<",
+            TaskStatus::Starred    => "*",
+        }
+    }
+
+    /// the name used in json output
+    pub fn name(&self) -> &str {
+        match self {
+            TaskStatus::Open       => "open",
+            TaskStatus::Done       => "done",
+            TaskStatus::Cancelled  => "cancelled",
+            TaskStatus::InProgress => "in-progress",
+            TaskStatus::Forwarded  => "forwarded",
+            TaskStatus::Scheduled  => "scheduled",
+            TaskStatus::Starred    => "starred",
+        }
+    }
+
+    /// the glyph used by `list`, None for the default checkbox
+    pub fn glyph(&self) -> Option<&str> {
+        match self {
+            TaskStatus::Open       => None,
+            TaskStatus::Done       => Some(CHECKED),
+            TaskStatus::Cancelled  => Some(CANCELLED),
+            TaskStatus::InProgress => Some(INPROGRESS),
+            TaskStatus::Forwarded  => Some(FORWARDED),
+            TaskStatus::Scheduled  => Some(SCHEDULED),
+            TaskStatus::Starred    => Some(STARRED),
+        }
+    }
+}
+
+impl From<MarkAs> for TaskStatus {
+    fn from(mark_as: MarkAs) -> Self {
+        match mark_as {
+            MarkAs::Done       => TaskStatus::Done,
+            MarkAs::Cancelled  => TaskStatus::Cancelled,
+            MarkAs::InProgress => TaskStatus::InProgress,
+            MarkAs::Forwarded  => TaskStatus::Forwarded,
+            MarkAs::Scheduled  => TaskStatus::Scheduled,
+            MarkAs::Starred    => TaskStatus::Starred,
+        }
+    }
+}
+
+/// end conditions and states of routine rule, as tokens after the start date
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoutineOpts {
+    /// "until:YYYY-MM-DD", no more checkouts after the date
+    pub until: Option<String>,
+    /// "times:N/MAX", checked out N times of at most MAX
+    pub times: Option<(u32, u32)>,
+    /// "paused", or "paused:YYYY-MM-DD" to resume after the date
+    pub paused: bool,
+    pub paused_until: Option<String>,
+    /// "skip:YYYY-MM-DD", the next occurrence to skip
+    pub skip: Option<String>,
+    /// "catchup:collapse|each|off", how to checkout the missed occurrences
+    pub catchup: Option<RoutineCatchup>,
+    /// "last:YYYY-MM-DD", the date of last checkout
+    pub last: Option<String>,
+}
+
+impl RoutineOpts {
+    pub fn parse(tokens: &str) -> Self {
+        let mut opts = Self::default();
+        for token in tokens.split_whitespace() {
+            let (key, value) = token.split_once(':').unwrap_or((token, ""));
+            match key {
+                "until" => opts.until = Some(value.to_string()),
+                "times" => opts.times = value.split_once('/')
+                                 .and_then(|(n, max)| Some((n.parse().ok()?, max.parse().ok()?))),
+                "paused" => {
+                    opts.paused = true;
+                    opts.paused_until = (!value.is_empty()).then(|| value.to_string());
+                }
+                "skip" => opts.skip = Some(value.to_string()),
+                "catchup" => opts.catchup = RoutineCatchup::from_str(value, true).ok(),
+                "last" => opts.last = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        opts
+    }
+
+    /// no more occurrences on and after the date
+    pub fn is_ended(&self, date: &str) -> bool {
+        self.until.as_ref().is_some_and(|until| date > until.as_str()) ||
+        self.times.is_some_and(|(n, max)| n >= max)
+    }
+
+    pub fn is_paused(&self, date: &str) -> bool {
+        self.paused && self.paused_until.as_ref().is_none_or(|until| date <= until.as_str())
+    }
+}
+
+// as " until:YYYY-MM-DD times:N/MAX paused skip:YYYY-MM-DD ...", empty if none
+impl fmt::Display for RoutineOpts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(until) = &self.until { write!(f, " until:{}", until)? }
+        if let Some((n, max)) = self.times { write!(f, " times:{}/{}", n, max)? }
+        if self.paused {
+            match &self.paused_until {
+                Some(until) => write!(f, " paused:{}", until)?,
+                None => write!(f, " paused")?,
+            }
+        }
+        if let Some(skip) = &self.skip { write!(f, " skip:{}", skip)? }
+        if let Some(catchup) = self.catchup {
+            write!(f, " catchup:{}", catchup.to_possible_value().unwrap().get_name())?
+        }
+        if let Some(last) = &self.last { write!(f, " last:{}", last)? }
+        Ok(())
+    }
+}
+
+// set the options of routine rule text, None if it's not a rule
+fn with_routine_opts(text: &str, opts: &RoutineOpts) -> Option<String> {
+    let caps = RE_ROUTINES.captures(text)?;
+    let mut text = text.to_string();
+    text.replace_range(caps.get(3).unwrap().range(), &opts.to_string());
+    Some(text)
+}
+
+/// routine info parsed from task text
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoutineInfo {
+    /// routine rule in ROUTINES box, e.g. `{󰃯:d 2024-10-01Tue 󰳟}`
+    Rule { kind: String, start_date: String, opts: RoutineOpts },
+    /// checked out instance in date boxes, e.g. `{󰃯:daily}`
+    Checkout { kind: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    /// unique in the box, assigned in file order by load()
+    pub id: usize,
+    pub text: String,
+    pub status: TaskStatus,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+
+    // metadata parsed from text
+    pub routine: Option<RoutineInfo>,
+    pub date_stamp: Option<String>,
+    pub tags: Vec<String>,
+    pub contexts: Vec<String>,
+    /// 'A' as the highest, from "(A)" or "!!!"
+    pub priority: Option<char>,
+    /// date in "due:YYYY-MM-DD"
+    pub due: Option<String>,
+    /// "not before" date in "t:YYYY-MM-DD"
+    pub tickler: Option<String>,
+
+    /// leading whitespaces in md file
+    pub indent: String,
+    /// the original line in md file, None for new ones
+    pub raw: Option<String>,
+}
+
+impl Task {
+    pub fn new(id: usize, text: &str, status: TaskStatus) -> Self {
+        let mut task = Self {
+            id,
+            text: text.to_string(),
+            status,
+            parent: None,
+            children: vec![],
+            routine: None,
+            date_stamp: None,
+            tags: vec![],
+            contexts: vec![],
+            priority: None,
+            due: None,
+            tickler: None,
+            indent: String::new(),
+            raw: None,
+        };
+        task.parse_meta();
+        task
+    }
+
+    /// closed ones: done or cancelled, all others are still pending
+    pub fn is_done(&self) -> bool {
+        matches!(self.status, TaskStatus::Done | TaskStatus::Cancelled)
+    }
+
+    pub fn is_sub(&self) -> bool {
+        self.parent.is_some()
+    }
+
+    /// the md line of task, the original one will be kept if not changed
+    pub fn to_line(&self) -> String {
+        if let Some(raw) = &self.raw {
+            if parse_task_line(raw.trim_end()) ==
+                Some((self.indent.clone(), self.status, self.text.clone())) {
+                return raw.clone()
+            }
+        }
+
+        format!("{}- [{}] {}", self.indent, self.status.to_char(), self.text.trim_end())
+    }
+
+    fn parse_meta(&mut self) {
+        self.routine = if let Some(caps) = RE_ROUTINES.captures(&self.text) {
+            Some(RoutineInfo::Rule { kind: caps[1].to_lowercase(),
+                                     start_date: caps[2].to_string(),
+                                     opts: RoutineOpts::parse(&caps[3]) })
+        } else {
+            RE_ROUTINES_CHECKOUT.captures(&self.text)
+                .map(|caps| RoutineInfo::Checkout { kind: caps[1].to_string() })
+        };
+        self.date_stamp = RE_DATESTAMP.captures(&self.text).map(|caps| caps[1].to_string());
+        self.tags = RE_TAGS.captures_iter(&self.text).map(|caps| caps[1].to_string()).collect();
+        self.contexts = RE_CONTEXTS.captures_iter(&self.text).map(|caps| caps[1].to_string()).collect();
+        self.priority = RE_PRIORITY.captures(&self.text).map(|caps| parse_priority(&caps[2]));
+        self.due = RE_DUE.captures(&self.text).map(|caps| caps[2].to_string());
+        self.tickler = RE_TICKLER.captures(&self.text).map(|caps| caps[2].to_string());
+    }
+
+    // for sorting, the ones without priority go last
+    fn priority_rank(&self) -> char {
+        self.priority.unwrap_or(char::MAX)
+    }
+}
+
+/// to filter tasks by #tags and @contexts, all of them should be there
+#[derive(Debug, Clone, Default)]
+pub struct TaskFilter {
+    pub tags: Vec<String>,
+    pub contexts: Vec<String>,
+}
+
+impl TaskFilter {
+    pub fn new(tags: &[String], contexts: &[String]) -> Self {
+        Self {
+            tags: tags.iter().map(|t| t.trim_start_matches('#').to_string()).collect(),
+            contexts: contexts.iter().map(|c| c.trim_start_matches('@').to_string()).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.contexts.is_empty()
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        self.tags.iter().all(|tag| task.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))) &&
+        self.contexts.iter().all(|ctx| task.contexts.iter().any(|c| c.eq_ignore_ascii_case(ctx)))
+    }
+
+    /// append the missing #tags and @contexts to the text
+    pub fn append_to(&self, text: &str) -> String {
+        let mut words :Vec<String> = text.split_whitespace().map(String::from).collect();
+        let tokens = self.tags.iter().map(|t| format!("#{}", t))
+                         .chain(self.contexts.iter().map(|c| format!("@{}", c)));
+
+        let mut text = text.to_string();
+        for token in tokens {
+            if ! words.contains(&token) {
+                text = text + " " + &token;
+                words.push(token);
+            }
+        }
+        text
+    }
+}
+
+/// one line in md file: any text(kept as it is) or a task
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoxLine {
+    Text(String),
+    Task(usize),
+}
+
+/// item to be selected by `mark`/`collect` etc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskItem {
+    pub id: usize,
+    pub label: String,
+    /// done major task with open sub-tasks, only as hint
+    pub warn: bool,
+}
+
+impl fmt::Display for TaskItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+/// to select pending tasks without interaction
+#[derive(Debug, Clone)]
+pub enum TaskSelector {
+    /// 1-based number as shown by `list --numbered`
+    Index(usize),
+    /// exact task text
+    Text(String),
+    Match(Regex),
+    /// with #tags and @contexts
+    Filter(TaskFilter),
+    All,
+}
+
+impl TaskSelector {
+    /// parse from cli input: number, `/regex/` or exact text
+    pub fn parse(input: &str) -> Result<Self> {
+        if let Ok(n) = input.parse::<usize>() {
+            Ok(TaskSelector::Index(n))
+        } else if input.len() > 2 && input.starts_with('/') && input.ends_with('/') {
+            Ok(TaskSelector::Match(Regex::new(&input[1..input.len()-1])?))
+        } else {
+            Ok(TaskSelector::Text(input.to_string()))
+        }
+    }
+}
+
+/// routine info in json output
+#[derive(Debug, Serialize)]
+pub struct RoutineJson {
+    /// "rule" in ROUTINES box, or "checkout" in date boxes
+    #[serde(rename = "type")]
+    pub kind_of: String,
+    pub kind: String,
+    pub start_date: Option<String>,
+    /// end conditions and states of rule
+    pub until: Option<String>,
+    pub count: Option<u32>,
+    pub max: Option<u32>,
+    pub paused: bool,
+    pub skip: Option<String>,
+    /// the date of last checkout
+    pub last: Option<String>,
+}
+
+/// task in json output
+#[derive(Debug, Serialize)]
+pub struct TaskJson {
+    pub id: usize,
+    pub text: String,
+    pub status: String,
+    pub done: bool,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub depth: usize,
+    pub routine: Option<RoutineJson>,
+    pub date_stamp: Option<String>,
+    pub tags: Vec<String>,
+    pub contexts: Vec<String>,
+    pub priority: Option<String>,
+    pub due: Option<String>,
+    pub tickler: Option<String>,
+}
+
+/// taskbox in json output
+#[derive(Debug, Serialize)]
+pub struct BoxJson {
+    pub schema: u32,
+    #[serde(rename = "box")]
+    pub name: String,
+    pub alias: String,
+    pub encrypted: bool,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks: Option<Vec<TaskJson>>,
+}
+
+/// one line of ndjson output for tasks
+#[derive(Debug, Serialize)]
+struct TaskLine<'a> {
+    schema: u32,
+    #[serde(rename = "box")]
+    name: &'a str,
+    alias: &'a str,
+    #[serde(flatten)]
+    task: &'a TaskJson,
+}
+
+/// upcoming occurrence of routine rule, in json output of `routines --upcoming`
+#[derive(Debug, Serialize)]
+pub struct OccurrenceJson {
+    pub date: String,
+    /// id of the rule in ROUTINES box
+    pub id: usize,
+    pub kind: String,
+    pub text: String,
+}
+
 #[derive(Debug)]
 pub struct TaskBox {
     pub fpath: PathBuf,
     pub tbname: String,
     pub alias: Option<String>,
-    pub tasks: Vec<(String, bool)>,
-    pub selected: Option<Vec<String>>,
+    pub tasks: Vec<Task>,
+    pub lines: Vec<BoxLine>,
+    pub selected: Option<Vec<usize>>,
     pub encrypted: bool,
     pub passwd_mem: Option<String>,
 }
@@ -54,6 +591,7 @@ impl TaskBox {
             tbname,
             alias: None, // None means not loaded
             tasks: vec![],
+            lines: vec![],
             selected: None,
             encrypted,
             passwd_mem: None,
@@ -67,13 +605,202 @@ impl TaskBox {
         sib
     }
 
+    pub fn get(&self, id: usize) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+
+    fn _get_mut(&mut self, id: usize) -> Option<&mut Task> {
+        self.tasks.iter_mut().find(|t| t.id == id)
+    }
+
+    fn _label(&self, task: &Task) -> String {
+        let text = match task.status.glyph() {
+            Some(glyph) if !task.is_done() => format!("{} {}", glyph, task.text),
+            _ => task.text.clone(),
+        };
+        match self._depth(task) {
+            0 => text,
+            depth => "  ".repeat(depth - 1) + PREFIX_SUBT + &text,
+        }
+    }
+
+    // nesting level, 0 for major tasks
+    fn _depth(&self, task: &Task) -> usize {
+        let mut depth = 0;
+        let mut parent = task.parent;
+        while let Some(pid) = parent {
+            depth += 1;
+            parent = self.get(pid).and_then(|t| t.parent);
+        }
+        depth
+    }
+
+    // tickler task(or its parents) before its date, only in INBOX and named boxes
+    fn _is_deferred(&self, task: &Task) -> bool {
+        if box_period(&self.tbname).is_some() { return false }
+
+        let today = get_today();
+        let mut cur = Some(task);
+        while let Some(t) = cur {
+            if t.tickler.as_ref().is_some_and(|d| *d > today) { return true }
+            cur = t.parent.and_then(|pid| self.get(pid));
+        }
+        false
+    }
+
+    fn _has_pending_ancestor(&self, task: &Task) -> bool {
+        let mut parent = task.parent;
+        while let Some(pid) = parent {
+            let Some(ptask) = self.get(pid) else { break };
+            if ! ptask.is_done() { return true }
+            parent = ptask.parent;
+        }
+        false
+    }
+
+    // the task and all its sub-tasks at any level, in file order
+    fn _subtree(&self, id: usize) -> Vec<usize> {
+        let mut ids = vec![id];
+        if let Some(task) = self.get(id) {
+            for &c in &task.children {
+                ids.extend(self._subtree(c))
+            }
+        }
+        ids
+    }
+
+    // set the indent of the task, and keep the relative ones of its sub-tree
+    fn _reindent(&mut self, id: usize, indent: &str) {
+        let Some(task) = self._get_mut(id) else { return };
+        let old = std::mem::replace(&mut task.indent, indent.to_string());
+
+        for c in task.children.clone() {
+            let rest = self.get(c).and_then(|t| t.indent.strip_prefix(&old))
+                           .filter(|rest| !rest.is_empty())
+                           .unwrap_or("  ").to_string();
+            self._reindent(c, &(indent.to_owned() + &rest));
+        }
+    }
+
+    // the last task in the sub-tree of the task
+    fn _subtree_end(&self, id: usize) -> usize {
+        let mut last = id;
+        while let Some(&c) = self.get(last).and_then(|t| t.children.last()) {
+            last = c
+        }
+        last
+    }
+
+    // add one task as the last child of `parent`, or to the end of all tasks
+    fn _push_task(&mut self, text: &str, status: TaskStatus, parent: Option<usize>) -> usize {
+        let id = self.tasks.iter().map(|t| t.id + 1).max().unwrap_or(0);
+        let mut task = Task::new(id, text, status);
+
+        let parent = parent.filter(|&pid| self.get(pid).is_some());
+        let after = match parent {
+            Some(pid) => Some(self._subtree_end(pid)),
+            None => self.tasks.last().map(|t| t.id),
+        };
+
+        if let Some(pid) = parent {
+            let ptask = self._get_mut(pid).unwrap();
+            ptask.children.push(id);
+            task.indent = ptask.indent.clone() + "  ";
+            task.parent = Some(pid);
+        }
+
+        // new line goes after the `after` task, or the end of file if no task yet
+        let (tpos, lpos) = match after {
+            Some(aid) => (self.tasks.iter().position(|t| t.id == aid).unwrap() + 1,
+                          self.lines.iter().position(|l| *l == BoxLine::Task(aid))
+                                           .map_or(self.lines.len(), |p| p + 1)),
+            None => (self.tasks.len(), self.lines.len()),
+        };
+        self.tasks.insert(tpos, task);
+        self.lines.insert(lpos, BoxLine::Task(id));
+        id
+    }
+
+    // remove one task, its children will be lifted to its parent
+    fn _remove_task(&mut self, id: usize) {
+        let Some(pos) = self.tasks.iter().position(|t| t.id == id) else { return };
+        let task = self.tasks.remove(pos);
+        self.lines.retain(|l| *l != BoxLine::Task(id));
+
+        for &c in &task.children {
+            self._reindent(c, &task.indent);
+            if let Some(child) = self._get_mut(c) {
+                child.parent = task.parent;
+            }
+        }
+        if let Some(ptask) = task.parent.and_then(|pid| self._get_mut(pid)) {
+            if let Some(cpos) = ptask.children.iter().position(|&c| c == id) {
+                ptask.children.splice(cpos..=cpos, task.children);
+            }
+        }
+    }
+
+    // replace all tasks, rebuild the children lists and the tree order by parent links
+    // the task lines in file will be refilled in the new order
+    fn _set_tasks(&mut self, tasks: Vec<Task>) {
+        let ids :HashSet<usize> = tasks.iter().map(|t| t.id).collect();
+        let mut children :HashMap<Option<usize>, Vec<Task>> = HashMap::new();
+        for mut task in tasks {
+            task.children.clear();
+            task.parent = task.parent.filter(|p| ids.contains(p));
+            children.entry(task.parent).or_default().push(task);
+        }
+
+        fn walk(parent: Option<usize>,
+                indent: &str,
+                children: &mut HashMap<Option<usize>, Vec<Task>>,
+                out: &mut Vec<Task>) {
+            for mut task in children.remove(&parent).unwrap_or_default() {
+                let id = task.id;
+                task.children = children.get(&Some(id))
+                                        .map(|v| v.iter().map(|t| t.id).collect())
+                                        .unwrap_or_default();
+                if parent.is_none() {
+                    task.indent.clear()
+                } else if task.indent.len() <= indent.len() {
+                    task.indent = indent.to_owned() + "  "
+                }
+
+                let indent = task.indent.clone();
+                out.push(task);
+                walk(Some(id), &indent, children, out);
+            }
+        }
+
+        let mut out = Vec::new();
+        walk(None, "", &mut children, &mut out);
+        self.tasks = out;
+
+        let mut ids = self.tasks.iter().map(|t| t.id);
+        let mut lines = Vec::new();
+        let mut last_pos = None;
+        for line in self.lines.drain(..) {
+            if let BoxLine::Task(_) = line {
+                if let Some(id) = ids.next() {
+                    lines.push(BoxLine::Task(id));
+                    last_pos = Some(lines.len());
+                }
+            } else {
+                lines.push(line)
+            }
+        }
+        let pos = last_pos.unwrap_or(lines.len());
+        lines.splice(pos..pos, ids.map(BoxLine::Task));
+        self.lines = lines;
+    }
+
     fn _load_file(&mut self) -> String {
         if self.encrypted {
             let passwd = i_getpass(false, Some("the password for encrypted box:"));
             self.passwd_mem = Some(passwd.clone());
             self._load_file_with_pass(&passwd).unwrap_or_else(|_| {
                 println!("{}", S_failure!("Invalid password."));
-                std::process::exit(1);
+                journal::exit(1);
             })
         } else {
             fs::read_to_string(&self.fpath).expect("Failed to read file")
@@ -92,6 +819,7 @@ impl TaskBox {
                              .unwrap()
                              .to_string();
 
+            journal::track(fpath);
             fs::create_dir_all(fpath.parent().unwrap()).expect("Failed to create basedir");
             fs::File::create(fpath).expect("Failed to create file");
             fs::write(fpath, format!("# {}\n\n", title)).expect("Failed to write file");
@@ -105,81 +833,86 @@ impl TaskBox {
                 let eguard = StderrOverride::override_file(null).unwrap();
 
                 self.collect_from(&mut self.sibling(ROUTINE_BOXNAME));
+                if title == get_today() {
+                    self.collect_ticklers();
+                }
 
                 drop(guard); drop(eguard);
             }
         }
 
-        let mut raw_names = HashSet::new();
-        let mut tasks = Vec::new();
-        let mut title = String::new();
+        let mut tasks :Vec<Task> = Vec::new();
+        let mut lines = Vec::new();
+        let mut title = None;
+        // (indent width, id) of the possible parents
+        let mut stack :Vec<(usize, usize)> = Vec::new();
 
-        let mut postfix_sub = String::new();
-        let mut last_is_sub = false;
+        let mut front_matter = false;
+        let mut in_code = false;
 
         for (index, rline) in self._load_file().lines().enumerate() {
-
             let line = rline.trim_end();
-            if index == 0 {
-                title = line.trim_start_matches("# ").to_string();
-
-            } else if line.starts_with("- [") {
-                if let Some(caps) = RE_PREFIX_OPEN.captures(line) {
-                    if raw_names.contains(&caps[1]) {
-                        // hack way to avoid duplicate task name troubles
-                        let mut newname = caps[1].to_string() + " ";
-                        while raw_names.contains(&newname) {
-                            // for multiple times duplicating
-                            newname.push(' ')
-                        }
-                        raw_names.insert(String::from(&newname));
-                        tasks.push((newname, false));
-                    } else {
-                        raw_names.insert(String::from(&caps[1]));
-                        tasks.push((caps[1].to_string(), false))
-                    }
-                } else if let Some(caps) = RE_PREFIX_DONE.captures(line) {
-                    tasks.push((caps[1].to_string(), true))
-                } else { continue }
 
-                if last_is_sub {
-                    last_is_sub = false;
-                    postfix_sub += " "; // hack way to identify sub-tasks belong to diff task
+            // any non-task lines will be kept as they are
+            if index == 0 && line == "---" {
+                front_matter = true;
+            } else if front_matter {
+                front_matter = line != "---";
+            } else if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+                in_code = !in_code;
+            } else if ! in_code {
+                if line.starts_with('#') {
+                    if title.is_none() && line.starts_with("# ") {
+                        title = Some(line.trim_start_matches("# ").to_string());
+                    }
+                    stack.clear();
                 }
-            } else {
-                // might be sub-tasks
-                let line = line.trim_start();
 
-                if let Some(caps) = RE_PREFIX_OPEN.captures(line) {
-                    tasks.push((PREFIX_SUBT.to_owned() + &caps[1] + &postfix_sub, false))
-                } else if let Some(caps) = RE_PREFIX_DONE.captures(line) {
-                    tasks.push((PREFIX_SUBT.to_owned() + &caps[1] + &postfix_sub, true))
-                } else { continue }
+                if let Some((indent, status, text)) = parse_task_line(line) {
+                    let mut task = Task::new(tasks.len(), &text, status);
+
+                    // more indented ones are sub-tasks
+                    let width = indent_width(&indent);
+                    while stack.last().is_some_and(|&(w, _)| w >= width) {
+                        stack.pop();
+                    }
+                    if let Some(&(_, pid)) = stack.last() {
+                        task.parent = Some(pid);
+                        tasks[pid].children.push(task.id);
+                    }
+                    stack.push((width, task.id));
+                    task.indent = indent;
+                    task.raw = Some(rline.to_string());
 
-                last_is_sub = true;
+                    lines.push(BoxLine::Task(task.id));
+                    tasks.push(task);
+                    continue
+                }
             }
+
+            lines.push(BoxLine::Text(rline.to_string()));
         }
 
-        self.alias = Some(get_box_alias(&title));
+        self.alias = Some(get_box_alias(&title.unwrap_or(self.tbname.clone())));
         self.tasks = tasks;
+        self.lines = lines;
     }
 
     fn _dump(&mut self) -> Result<()> {
-        let mut content = format!("# {}\n\n", self.tbname.clone());
-
-        for (mut task, done) in self.tasks.clone() {
-            task = task.trim_end().to_string();
+        let mut content = String::new();
 
-            if let Some(left) = task.strip_prefix(PREFIX_SUBT) {
-                content.push_str("  ");
-                task = left.to_string();
+        for line in &self.lines {
+            match line {
+                BoxLine::Text(text) => content.push_str(text),
+                BoxLine::Task(id) => match self.get(*id) {
+                    Some(task) => content.push_str(&task.to_line()),
+                    None => continue,
+                }
             }
-
-            if done { content.push_str(PREFIX_DONE) }
-            else {    content.push_str(PREFIX_OPEN) }
-            content.push_str(&(task + "\n"))
+            content.push('\n');
         }
 
+        journal::track(&self.fpath);
         if self.encrypted {
             self._dump_with_passwd(&content, self.passwd_mem.as_ref().unwrap())?
         } else {
@@ -190,49 +923,99 @@ impl TaskBox {
         Ok(())
     }
 
-    // mark the task which has "done" subtask as "done"
+    // mark the task which has "done" subtask(at any level) as "done"
     // return whether happened
-    fn _mark_task_with_done_subtask(&mut self, task: &str) -> bool {
-        if task.starts_with(PREFIX_SUBT) { return false }
-
-        let mut found = false;
-        let mut task_status :Option<&mut bool> = None;
-        for (t, done) in self.tasks.iter_mut() {
-            if ! found {
-                if t == task && ! *done {
-                    found = true;
-                    task_status = Some(done);
-                }
-                continue
-
-            } else if ! t.starts_with(PREFIX_SUBT) {
-                return false
-            } else if *done {
-                // found done sub-task for this major task
-                if let Some(task_status) = task_status {
-                    *task_status = true;
-                }
-                return true
-            }
+    fn _mark_task_with_done_subtask(&mut self, id: usize) -> bool {
+        let Some(task) = self.get(id) else { return false };
+        if task.is_done() { return false }
+        if self._policy() == SubtaskPolicy::Independent { return false }
+
+        let has_done_sub = self._subtree(id).into_iter().skip(1)
+                               .any(|c| self.get(c).is_some_and(|t| t.is_done()));
+        if has_done_sub {
+            self._get_mut(id).unwrap().status = TaskStatus::Done;
         }
-        false
+        has_done_sub
     }
 
-    fn _move_one(&mut self, from: &mut TaskBox, task: &str) {
-        // just append it without dup checking, on purpuse
-        self.tasks.push((task.to_string(), false));
+    fn _policy(&self) -> SubtaskPolicy {
+        CONFIG.read().unwrap().get_subtask_policy(&self.tbname)
+    }
+
+    // with "all" policy, close the ancestors whose sub-tasks are all done
+    fn _close_parents(&mut self, id: usize) {
+        let mut parent = self.get(id).and_then(|t| t.parent);
+        while let Some(pid) = parent {
+            let ptask = self.get(pid).unwrap();
+            if ptask.is_done() ||
+               ! ptask.children.iter().all(|&c| self.get(c).is_some_and(|t| t.is_done())) { break }
 
-        if ! from._mark_task_with_done_subtask(task) {
-            // remove the one from "from"
-            #[allow(clippy::nonminimal_bool)]
-            from.tasks.retain(|(t, d)| ! (t == task && !d));
+            parent = ptask.parent;
+            self._get_mut(pid).unwrap().status = TaskStatus::Done;
         }
     }
 
+    // "n/m" of done/all sub-tasks
+    fn _progress(&self, task: &Task) -> Option<String> {
+        if task.children.is_empty() { return None }
+        let done = task.children.iter().filter(|&&c| self.get(c).is_some_and(|t| t.is_done())).count();
+        Some(format!("{}/{}", done, task.children.len()))
+    }
+
+    // return whether the task should be removed from "from"
+    fn _move_one(&mut self, from: &mut TaskBox, item: &TaskItem,
+                            moved: &mut HashMap<usize, usize>) -> bool {
+        // just append it without dup checking, on purpuse
+        if item.warn {
+            let newid = self._push_task(&item.label, TaskStatus::Open, None);
+            moved.insert(item.id, newid);
+            return false
+        }
+
+        let Some(task) = from.get(item.id).cloned() else { return false };
+        let parent = task.parent.map(|pid| match moved.get(&pid) {
+            Some(&newpid) => newpid,
+            None => self._copy_parents(from, pid, moved),
+        });
+        let status = match task.status {
+            TaskStatus::Forwarded => TaskStatus::Open,
+            status => status,
+        };
+        let newid = self._push_task(&task.text, status, parent);
+        moved.insert(item.id, newid);
+
+        ! from._mark_task_with_done_subtask(item.id)
+    }
+
+    // parent(and its ancestors) not moved, to reuse or copy it in self
+    // return the id in self
+    fn _copy_parents(&mut self, from: &TaskBox, pid: usize,
+                                moved: &mut HashMap<usize, usize>) -> usize {
+        let ptask = from.get(pid).unwrap();
+        let grand = ptask.parent.map(|gid| match moved.get(&gid) {
+            Some(&newgid) => newgid,
+            None => self._copy_parents(from, gid, moved),
+        });
+
+        // "independent" ones keep the status of parent as it is
+        let (text, status) = if from._policy() == SubtaskPolicy::Independent {
+            (ptask.text.clone(), ptask.status)
+        } else if ptask.is_done() {
+            (WARN.to_owned() + " " + &ptask.text, TaskStatus::Open)
+        } else {
+            (ptask.text.clone(), TaskStatus::Open)
+        };
+        let newpid = self.tasks.iter()
+            .find(|t| t.parent == grand && t.status == status && t.text == text).map(|t| t.id)
+            .unwrap_or_else(|| self._push_task(&text, status, grand));
+
+        moved.insert(pid, newpid);
+        newpid
+    }
+
     fn _addone(&mut self, task: String) {
-        let pair = (task, false);
-        if ! self.tasks.contains(&pair) {
-            self.tasks.push(pair)
+        if ! self.tasks.iter().any(|t| !t.is_sub() && !t.is_done() && t.text == task) {
+            self._push_task(&task, TaskStatus::Open, None);
         }
     }
 
@@ -243,12 +1026,27 @@ impl TaskBox {
         self._dump().unwrap()
     }
 
+    // the tasks to go in collect_from()
+    fn _tasks_to_collect(&mut self) -> Vec<TaskItem> {
+        let tasks_in = self.get_all_to_mark();
+
+        // "independent" done sub-tasks go along with their pending parents
+        if self._policy() == SubtaskPolicy::Independent {
+            return self.tasks.iter()
+                .filter(|t| !t.is_done() || self._has_pending_ancestor(t))
+                .map(|t| TaskItem { id: t.id, label: self._label(t), warn: false })
+                .collect()
+        }
+        tasks_in
+    }
+
     pub fn collect_from(&mut self, tb_from: &mut TaskBox) {
-        let tasks_in = tb_from.get_all_to_mark();
+        let mut tasks_in = tb_from._tasks_to_collect();
         if tasks_in.is_empty() { return }
 
         if let Some(ref selected) = tb_from.selected {
-            if selected.is_empty() { return }
+            if selected.is_empty() &&
+               ! tb_from.tasks.iter().any(|t| t.status == TaskStatus::Forwarded) { return }
         }
 
         // print title line
@@ -258,50 +1056,52 @@ impl TaskBox {
                                 S_moveto!(to), PROGRESS);
 
         // postpone self.load() to avoid stdio chaos(from daily hook)
+        let fresh = ! self.fpath.exists();
         self.load();
 
-        for task in tasks_in {
-            if let Some(ref selected) = tb_from.selected {
-                if ! selected.contains(&task) { continue }
-            }
+        // the daily hook of the new box may change the source(e.g. routines checked out), reload it
+        if fresh {
+            tb_from.alias = None;
+            tasks_in = tb_from._tasks_to_collect();
+        }
 
-            let caps = RE_ROUTINES.captures(&task);
+        // selected ones will take all their sub-tasks along
+        let selected :Option<HashSet<usize>> = tb_from.selected.as_ref().map(|selected|
+            selected.iter().flat_map(|&id| tb_from._subtree(id)).collect());
+
+        let mut moved = HashMap::new();
+        let mut to_remove = Vec::new();
+        for item in tasks_in {
+            let Some(task) = tb_from.get(item.id).cloned() else { continue };
+            if let Some(ref selected) = selected {
+                // "forwarded" ones will always go
+                if ! selected.contains(&item.id) && task.status != TaskStatus::Forwarded { continue }
+            }
 
             if from == ROUTINE_BOXNAME {
                 // non-routine tasks in routine box will be skipped
-                // only "collect --inbox routines" (routines -> today/tomo) is valid
-                if to != "today" && to != "tomorrow" { continue }
-
-                if let Some(caps) = caps {
-                    if ! util::match_routine(&caps[1], &caps[2], &to) {continue}
-
-                    let kind = match &caps[1] {
-                        "d" => "daily",
-                        "w" => "weekly",
-                        "b" => "biweekly",
-                        "q" => "qweekly",
-                        "m" => "monthly",
-                        "1" => "reminder",
-                        _ => "unknown",
-                    };
-                    let checkout_date = match to.as_ref() {
-                        "today" => get_today(),
-                        "tomorrow" => get_tomorrow(),
-                        _ => panic!("unsupported checkout date(only today/tomorrow)"),
-                    };
-                    let newtask = format!("{{{}:{}}} {} [{} {}]",
-                                           ROUTINES, kind, &caps[3], DATESTAMP, checkout_date);
-
-                    println!("  {} : {}", S_checkbox!(ROUTINES), newtask);
-
-                    let pair = (newtask, false);
-                    if ! self.tasks.contains(&pair) {
-                        self.tasks.push(pair)
-                    }
+                // only "collect --inbox routines" (routines -> date boxes) is valid
+                if ! is_date_box(&self.tbname) { continue }
+
+                if let Some(caps) = RE_ROUTINES.captures(&task.text) {
+                    let checkout_date = self.tbname.clone();
+                    let (opts, checked_out, ended) = self._checkout_rule(&task.text, &checkout_date);
 
                     // clean up "once reminder"
-                    if kind == "reminder" {
-                        tb_from.tasks.retain(|(_task, _)| _task != &task)
+                    if checked_out && Routine::parse(&caps[1]) == Ok(Routine::Once) {
+                        to_remove.push(task.id)
+                    }
+
+                    if ended {
+                        println!("  {} : {} {}", S_checked!(CHECKED), S_warning!("ended:"), &caps[4]);
+                    }
+                    if ended || opts != RoutineOpts::parse(&caps[3]) {
+                        let text = with_routine_opts(&task.text, &opts).unwrap();
+                        if let Some(rule) = tb_from._get_mut(task.id) {
+                            rule.text = text;
+                            rule.parse_meta();
+                            if ended { rule.status = TaskStatus::Done }
+                        }
                     }
                 } else {
                     // ignore non-routine task
@@ -309,62 +1109,197 @@ impl TaskBox {
                             S_failure!(WARN),
                             S_checkbox!(CHECKBOX),
                             S_warning!("skip:"),
-                            task);
+                            item);
                     continue
                 }
 
             } else {
 
-                if task.contains(WARN) {
-                    println!("  {} : {}", S_checkbox!(CHECKED), task);
-                } else if caps.is_some() {
+                if item.label.contains(WARN) {
+                    println!("  {} : {}", S_checked!(CHECKED), item);
+                } else if matches!(task.routine, Some(RoutineInfo::Rule{..})) {
                     println!("{} {} : {}",
                             S_failure!(WARN),
                             S_checkbox!(CHECKBOX),
-                            task);
-                } else if RE_ROUTINES_CHECKOUT.is_match(&task) && to == INBOX_BOXNAME {
+                            item);
+                } else if matches!(task.routine, Some(RoutineInfo::Checkout{..})) && to == INBOX_BOXNAME {
                     // ignore checkout routine task
                     println!("{} {} : {} {}",
                             S_failure!(WARN),
                             S_checkbox!(CHECKBOX),
                             S_warning!("skip:"),
-                            task);
+                            item);
                     continue
 
                 } else {
-                    println!("  {} : {}", S_checkbox!(CHECKBOX), task);
+                    println!("  {} : {}", S_checkbox!(CHECKBOX), item);
                 }
 
-                self._move_one(tb_from, &task);
+                if self._move_one(tb_from, &item, &mut moved) {
+                    to_remove.push(item.id)
+                }
             }
         }
+        to_remove.iter().for_each(|&id| tb_from._remove_task(id));
+
+        if box_period(&self.tbname).is_some() {
+            self._coalesce_routines();
+        }
 
         tb_from._dump().unwrap();
         self._dump().unwrap();
     }
 
+    // keep only the newest one of the open instances of the same routine
+    fn _coalesce_routines(&mut self) {
+        let how = CONFIG.read().unwrap().get_routine_coalesce(&self.tbname);
+        if how == RoutineCoalesce::Off { return }
+
+        let mut keys = Vec::new();
+        let mut instances :HashMap<String, Vec<&Task>> = HashMap::new();
+        for task in self.tasks.iter().filter(|t| !t.is_done()) {
+            if ! matches!(task.routine, Some(RoutineInfo::Checkout{..})) { continue }
+
+            let key = routine_instance_key(&task.text);
+            if ! instances.contains_key(&key) { keys.push(key.clone()) }
+            instances.entry(key).or_default().push(task);
+        }
+
+        let mut to_remove = Vec::new();
+        let mut to_update = Vec::new();
+        for key in keys {
+            let tasks = &instances[&key];
+            if tasks.len() < 2 { continue }
+
+            // the later one in box goes first for the same date
+            let newest = tasks.iter().max_by_key(|t| t.date_stamp.clone()).unwrap();
+            let older :Vec<_> = tasks.iter().filter(|t| t.id != newest.id).collect();
+            older.iter().for_each(|t| to_remove.push(t.id));
+
+            if how == RoutineCoalesce::Count {
+                let times = older.iter().map(|t| missed_times(&t.text) + 1).sum::<usize>()
+                            + missed_times(&newest.text);
+                to_update.push((newest.id, with_missed(&newest.text, times)));
+            }
+            println!("  {} : {} {}", S_checked!(CHECKED), S_warning!("coalesced:"), key);
+        }
+
+        to_remove.into_iter().for_each(|id| self._remove_task(id));
+        for (id, text) in to_update {
+            if let Some(task) = self._get_mut(id) {
+                task.text = text;
+                task.parse_meta();
+            }
+        }
+    }
+
+    // checkout routine rule to the date, together with the missed occurrences since
+    // the last checkout, returns the updated options, whether checked out and ended
+    fn _checkout_rule(&mut self, rule: &str, date_str: &str) -> (RoutineOpts, bool, bool) {
+        let caps = RE_ROUTINES.captures(rule).unwrap();
+        let (kind, text) = (&caps[1], &caps[4]);
+        let start_date = NaiveDate::parse_from_str(&caps[2], "%Y-%m-%d").unwrap();
+        let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d").unwrap();
+        let mut opts = RoutineOpts::parse(&caps[3]);
+
+        // the future ones are pre-filled only, without catch-up and the last date
+        let future = date_str > get_today().as_str();
+
+        // already checked out
+        if ! future && opts.last.as_ref().is_some_and(|last| last.as_str() >= date_str) {
+            return (opts, false, false)
+        }
+
+        // the missed ones, except the paused, skipped or ended ones
+        let catchup = opts.catchup.or(CONFIG.read().unwrap().routine_catchup).unwrap_or_default();
+        let last = opts.last.as_ref().and_then(|last| NaiveDate::parse_from_str(last, "%Y-%m-%d").ok());
+        let missed :Vec<String> = match last {
+            Some(last) if catchup != RoutineCatchup::Off && ! future => last.iter_days().skip(1)
+                .take_while(|&d| d < date)
+                .filter(|&d| util::match_routine_on(kind, start_date, d))
+                .map(|d| d.to_string())
+                .filter(|d| !opts.is_paused(d) && opts.skip.as_ref() != Some(d) &&
+                            opts.until.as_ref().is_none_or(|until| d <= until))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        // to checkout as (date stamp, the first missed date for "overdue since")
+        let mut instances :Vec<(String, Option<&String>)> = Vec::new();
+        if catchup == RoutineCatchup::Each {
+            instances.extend(missed.iter().map(|d| (d.clone(), None)));
+        }
+        let mut skipped = false;
+        if ! opts.is_ended(date_str) && util::match_routine_on(kind, start_date, date) {
+            if opts.skip.as_deref() == Some(date_str) {
+                skipped = true;
+                println!("  {} : {} {}", S_checkbox!(ROUTINES), S_warning!("skip:"), text);
+            } else if ! opts.is_paused(date_str) {
+                instances.push((date_str.to_string(), None))
+            }
+        }
+        if catchup == RoutineCatchup::Collapse && ! missed.is_empty() {
+            // folded into the one of the date, or the last missed one
+            match instances.last_mut() {
+                Some(instance) => instance.1 = missed.first(),
+                None => instances.push((missed.last().unwrap().clone(), missed.first())),
+            }
+        }
+
+        let mut checked_out = false;
+        for (date_stamp, since) in instances {
+            if opts.times.is_some_and(|(n, max)| n >= max) { break }
+
+            let overdue = since.map(|since| format!(" (overdue since {})", since)).unwrap_or_default();
+            let newtask = format!("{{{}:{}}} {}{} [{} {}]",
+                                   ROUTINES, routine_kind_name(kind), text, overdue, DATESTAMP, date_stamp);
+
+            // pre-filled one of the same date
+            let key = routine_instance_key(&newtask);
+            checked_out = true;
+            if self.tasks.iter().any(|t| t.date_stamp.as_ref() == Some(&date_stamp) &&
+                                         routine_instance_key(&t.text) == key) { continue }
+
+            println!("  {} : {}", S_checkbox!(ROUTINES), newtask);
+            self._addone(newtask);
+
+            if let Some((n, max)) = opts.times { opts.times = Some((n + 1, max)) }
+        }
+        if future {
+            // the days before are not checked out yet, and the skip is kept for the day itself
+            let ended = opts.times.is_some_and(|(n, max)| n >= max);
+            return (opts, checked_out, ended)
+        }
+        let ended = opts.is_ended(date_str);
+
+        // clean up the expired pausing and skipping, which will not be missed ones
+        let mut handled = Vec::new();
+        if opts.paused && ! opts.is_paused(date_str) {
+            handled.extend(opts.paused_until.take());
+            opts.paused = false;
+        }
+        if opts.skip.as_ref().is_some_and(|skip| skip.as_str() <= date_str) {
+            handled.extend(opts.skip.take());
+        }
+
+        if checked_out || skipped || opts.last.is_none() {
+            opts.last = Some(date_str.to_string());
+        } else if let Some(latest) = handled.into_iter().max() {
+            opts.last = opts.last.take().max(Some(latest));
+        }
+        (opts, checked_out, ended)
+    }
+
     pub fn add(&mut self, what: String,
                           routine: Option<Routine>,
                           add_date: bool,
                           start_date: &str) {
+        if let Some(routine) = routine {
+            return self.add_routine(what, routine, start_date, &RoutineOpts::default())
+        }
         self.load();
 
-        let task = if let Some(routine) = routine {
-            format!("{{{}:{} {}{} 󰳟}} {}",
-                ROUTINES,
-                match routine {
-                    Routine::Daily    => "d",
-                    Routine::Weekly   => "w",
-                    Routine::Biweekly => "b",
-                    Routine::Qweekly  => "q",
-                    Routine::Monthly  => "m",
-                    Routine::Once  => "1",
-                },
-                start_date,
-                weekday_from_date(start_date),
-                what)
-
-        } else if add_date {
+        let task = if add_date {
             format!("{} [{} {}]", what, DATESTAMP, get_today())
         } else { what };
 
@@ -372,34 +1307,185 @@ impl TaskBox {
         self._dump().unwrap()
     }
 
-    pub fn get_all_to_mark(&mut self) -> Vec<String> {
+    /// add routine rule with end conditions, e.g. `until:YYYY-MM-DD times:0/MAX`
+    pub fn add_routine(&mut self, what: String, routine: Routine, start_date: &str, opts: &RoutineOpts) {
         self.load();
 
-        let mut tasks = Vec::new();
-        let mut last_major_task :Option<(String, bool)> = None;
-        for (t, done) in &self.tasks {
-            if t.starts_with(PREFIX_SUBT) {
-                if let Some((ref last_major, lm_done)) = last_major_task {
-                    if lm_done && !done {
-                        tasks.push(WARN.to_owned() + " " + last_major);
-                        last_major_task = None;
-                    }
+        let task = format!("{{{}:{} {}{}{} 󰳟}} {}",
+            ROUTINES,
+            routine.token(),
+            start_date,
+            weekday_from_date(start_date),
+            opts,
+            what);
+
+        self._addone(task);
+        self._dump().unwrap()
+    }
+
+    /// update options of the routine rules, returns the count of updated ones
+    pub fn update_routines<F>(&mut self, ids: &[usize], update: F) -> usize
+        where F: Fn(&str, &str, &mut RoutineOpts) {
+        self.load();
+
+        let mut count = 0;
+        for &id in ids {
+            let Some(task) = self._get_mut(id) else { continue };
+            let Some(caps) = RE_ROUTINES.captures(&task.text) else { continue };
+
+            // with the kind and start date of rule
+            let mut opts = RoutineOpts::parse(&caps[3]);
+            update(&caps[1], &caps[2], &mut opts);
+            let text = with_routine_opts(&task.text, &opts).unwrap();
+            task.text = text;
+            task.parse_meta();
+            count += 1;
+        }
+        self._dump().unwrap();
+        count
+    }
+
+    // add sub-task to the end of each parent's children
+    pub fn add_sub(&mut self, what: String, parents: &[usize], add_date: bool) {
+        self.load();
+
+        let task = if add_date {
+            format!("{} [{} {}]", what, DATESTAMP, get_today())
+        } else { what };
+
+        for &pid in parents {
+            let Some(parent) = self.get(pid) else { continue };
+            let dup = parent.children.iter()
+                            .any(|&c| self.get(c).is_some_and(|t| !t.is_done() && t.text == task));
+            if ! dup {
+                self._push_task(&task, TaskStatus::Open, Some(pid));
+            }
+        }
+        self._dump().unwrap()
+    }
+
+    pub fn get_all_to_mark(&mut self) -> Vec<TaskItem> {
+        self.load();
+
+        let mut items = Vec::new();
+        let mut warned = HashSet::new();
+        let independent = self._policy() == SubtaskPolicy::Independent;
+        for t in &self.tasks {
+            if let Some(pid) = t.parent {
+                let parent = self.get(pid).unwrap();
+                if parent.is_done() && !independent && !t.is_done() && warned.insert(pid) {
+                    items.push(TaskItem {
+                        id: pid,
+                        label: WARN.to_owned() + " " + &parent.text,
+                        warn: true,
+                    });
                 }
-            } else {
-                last_major_task = Some((t.clone(), *done));
             }
-            if !done {
-                tasks.push(t.clone());
+            if !t.is_done() && !self._is_deferred(t) {
+                items.push(TaskItem { id: t.id, label: self._label(t), warn: false });
+            }
+        }
+
+        items
+    }
+
+    /// move the tickler tasks of the date from INBOX and named boxes to self(today)
+    pub fn collect_ticklers(&mut self) {
+        let today = get_today();
+        let Ok(entries) = fs::read_dir(self.fpath.parent().unwrap()) else { return };
+
+        let mut boxes :Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "md"))
+            .filter(|p| {
+                let name = p.file_stem().unwrap().to_str().unwrap();
+                box_period(name).is_none() && name != ROUTINE_BOXNAME
+            }).collect();
+        boxes.sort();
+
+        for fpath in boxes {
+            let mut tb_from = TaskBox::new(fpath);
+            tb_from.load();
+            let ids :Vec<_> = tb_from.tasks.iter()
+                .filter(|t| !t.is_done() && t.tickler.as_ref().is_some_and(|d| *d <= today))
+                .map(|t| t.id).collect();
+            if ids.is_empty() { continue }
+
+            tb_from.selected = Some(ids);
+            self.collect_from(&mut tb_from);
+        }
+    }
+
+    /// tasks with text matched, filtered by done(Some(true)) or pending(Some(false))
+    pub fn search<F: Fn(&str) -> bool>(&mut self, matches: F, done: Option<bool>) -> Vec<TaskItem> {
+        self.load();
+
+        self.tasks.iter()
+            .filter(|t| done.is_none_or(|done| t.is_done() == done))
+            .filter(|t| matches(&t.text))
+            .map(|t| TaskItem { id: t.id, label: self._label(t), warn: false })
+            .collect()
+    }
+
+    /// pending tasks matched by the selector
+    pub fn select(&mut self, selector: &TaskSelector) -> Vec<usize> {
+        self.load();
+
+        let pending = self.tasks.iter().filter(|t| !t.is_done());
+        match selector {
+            TaskSelector::Index(n) => pending.enumerate().filter(|(i, _)| i + 1 == *n)
+                                             .map(|(_, t)| t.id).collect(),
+            TaskSelector::Text(text) => pending.filter(|t| t.text == *text).map(|t| t.id).collect(),
+            TaskSelector::Match(re) => pending.filter(|t| re.is_match(&t.text)).map(|t| t.id).collect(),
+            TaskSelector::Filter(filter) => pending.filter(|t| filter.matches(t)).map(|t| t.id).collect(),
+            TaskSelector::All => pending.map(|t| t.id).collect(),
+        }
+    }
+
+    // tasks matched with their ancestors and sub-tasks, None for no filter
+    fn _filtered(&self, filter: &TaskFilter) -> Option<HashSet<usize>> {
+        if filter.is_empty() { return None }
+
+        let mut ids = HashSet::new();
+        for t in self.tasks.iter().filter(|t| filter.matches(t)) {
+            ids.extend(self._subtree(t.id));
+            let mut parent = t.parent;
+            while let Some(pid) = parent {
+                ids.insert(pid);
+                parent = self.get(pid).and_then(|p| p.parent);
+            }
+        }
+        Some(ids)
+    }
+
+    /// whether any pending(or any with listall) task matched
+    pub fn has_matched(&mut self, filter: &TaskFilter, listall: bool) -> bool {
+        self.load();
+        self.tasks.iter().any(|t| (listall || !t.is_done()) && filter.matches(t))
+    }
+
+    // tasks in tree order, siblings sorted by priority
+    fn _sorted_by_priority(&self) -> Vec<&Task> {
+        fn walk<'a>(tb: &'a TaskBox, mut siblings: Vec<&'a Task>, out: &mut Vec<&'a Task>) {
+            siblings.sort_by_key(|t| t.priority_rank());
+            for t in siblings {
+                out.push(t);
+                walk(tb, t.children.iter().filter_map(|&c| tb.get(c)).collect(), out);
             }
         }
 
-        tasks
+        let mut out = Vec::new();
+        walk(self, self.tasks.iter().filter(|t| !t.is_sub()).collect(), &mut out);
+        out
     }
 
-    pub fn list(&mut self, listall: bool, plain: bool) {
+    pub fn list(&mut self, listall: bool, plain: bool, numbered: bool, filter: &TaskFilter,
+                           by_priority: bool) {
         self.load();
-        let left : Vec<_> = self.tasks.iter().filter(|(_,done)| !done).map(|(task, _)| task.clone()).collect();
-        let dones : Vec<_> = self.tasks.iter().filter(|(_,done)| *done).map(|(task, _)| task.clone()).collect();
+        let shown = self._filtered(filter);
+        let is_shown = |t: &&Task| shown.as_ref().is_none_or(|ids| ids.contains(&t.id)) &&
+                                   (listall || !self._is_deferred(t));
+        let left : Vec<_> = self.tasks.iter().filter(|t| !t.is_done()).filter(is_shown).collect();
+        let dones : Vec<_> = self.tasks.iter().filter(|t| t.is_done()).filter(is_shown).collect();
 
         let checkbox_style = if self.tbname == "ROUTINES" {
             ROUTINES
@@ -409,7 +1495,11 @@ impl TaskBox {
 
         if listall && !dones.is_empty() {
             for t in dones {
-                println!("{}  {}", S_checked!(CHECKED), t.strikethrough())
+                if t.status == TaskStatus::Cancelled {
+                    println!("{}  {}", CANCELLED.bright_black(), self._label(t).strikethrough().bright_black())
+                } else {
+                    println!("{}  {}", S_checked!(CHECKED), self._label(t).strikethrough())
+                }
             }
             println!();
         }
@@ -418,42 +1508,69 @@ impl TaskBox {
             if ! plain { println!(" {} left!", S_empty!("nothing")); }
         } else {
             let mut msg;
-            let mut last_major_task :Option<(String, bool)> = None;
+            let mut warned = HashSet::new();
+            let independent = self._policy() == SubtaskPolicy::Independent;
             let mut last_is_sub = false;
 
-            for (t, done) in &self.tasks {
-                msg = format!("{}  ", S_blink!(S_checkbox!(checkbox_style)));
-                if t.starts_with(PREFIX_SUBT) {
-                    if *done { continue }
+            // numbers always follow the file order
+            let nums :HashMap<usize, usize> = self.tasks.iter().filter(|t| !t.is_done())
+                                                  .enumerate().map(|(i, t)| (t.id, i + 1)).collect();
+            let ordered = if by_priority {
+                self._sorted_by_priority()
+            } else {
+                self.tasks.iter().collect()
+            };
+
+            for t in ordered {
+                if ! is_shown(&t) { continue }
+
+                msg = match t.status {
+                    TaskStatus::InProgress => format!("{}  ", S_blink!(S_warning!(INPROGRESS))),
+                    TaskStatus::Starred => format!("{}  ", S_blink!(S_warning!(STARRED))),
+                    status => format!("{}  ", S_blink!(S_checkbox!(status.glyph().unwrap_or(checkbox_style)))),
+                };
+                let mut text = match t.status {
+                    TaskStatus::InProgress => t.text.bold().yellow().to_string(),
+                    TaskStatus::Starred => t.text.bold().to_string(),
+                    _ => highlight_tokens(&t.text),
+                };
+                if let Some(progress) = self._progress(t) {
+                    text = format!("{} {}", text, progress.bright_black());
+                }
+
+                if let Some(pid) = t.parent {
+                    if t.is_done() { continue }
 
-                    msg = format!("{} {}", S_blink!(SUBTASK), msg);
-                    msg += t.strip_prefix(PREFIX_SUBT).unwrap();
+                    let depth = self._depth(t);
+                    msg = format!("{}{} {}", "  ".repeat(depth - 1), S_blink!(SUBTASK), msg);
+                    msg += &text;
                     last_is_sub = true;
 
-                    if let Some((ref last_major, lm_done)) = last_major_task {
-                        if lm_done {
-                            println!("{} {} {}", S_checked!(CHECKED), WARN, last_major.strikethrough().bright_black());
-                            last_major_task = None;
-                        }
+                    let parent = self.get(pid).unwrap();
+                    if parent.is_done() && !independent && warned.insert(pid) {
+                        println!("{}{} {} {}", "  ".repeat(depth - 1),
+                                 S_checked!(CHECKED), WARN, parent.text.strikethrough().bright_black());
                     }
                 } else {
-                    last_major_task = Some((t.clone(), *done));
-
-                    if *done { continue }
+                    if t.is_done() { continue }
 
                     if last_is_sub {
                         last_is_sub = false;
-                        msg = "\n".to_owned() + &msg;
+                        if ! plain { println!() }
                     }
-                    msg = msg + t;
+                    msg += &text;
                 }
 
+                let numstr = match nums.get(&t.id) {
+                    Some(num) if numbered => format!("{:>2} ", num),
+                    _ => String::new(),
+                };
                 if plain {
-                    if !t.starts_with(PREFIX_SUBT) { println!("{}",
-                                    &t.replace(ROUTINES, ROUTINES_PLAIN)
-                                      .replace(DATESTAMP, DATESTAMP_PLAIN)); }
+                    if !t.is_sub() { println!("{}{}", numstr,
+                                    &t.text.replace(ROUTINES, ROUTINES_PLAIN)
+                                           .replace(DATESTAMP, DATESTAMP_PLAIN)); }
                 } else {
-                    println!("{}", msg);
+                    println!("{}{}", numstr.bright_black(), msg);
                 }
             }
         }
@@ -461,25 +1578,162 @@ impl TaskBox {
 
     pub fn count(&mut self) -> usize {
         self.load();
-        self.tasks.iter().filter(|(_, done)| !done).count()
+        self.tasks.iter().filter(|t| !t.is_done() && !self._is_deferred(t)).count()
+    }
+
+    fn _task_json(&self, task: &Task, listall: bool) -> TaskJson {
+        TaskJson {
+            id: task.id,
+            text: task.text.clone(),
+            status: task.status.name().to_string(),
+            done: task.is_done(),
+            parent: task.parent,
+            children: task.children.iter().copied()
+                          .filter(|&c| listall || self.get(c).is_some_and(|t| !t.is_done()))
+                          .collect(),
+            depth: self._depth(task),
+            routine: task.routine.as_ref().map(|routine| match routine {
+                RoutineInfo::Rule { kind, start_date, opts } => RoutineJson {
+                    kind_of: "rule".into(),
+                    kind: routine_kind_name(kind),
+                    start_date: Some(start_date.clone()),
+                    until: opts.until.clone(),
+                    count: opts.times.map(|(n, _)| n),
+                    max: opts.times.map(|(_, max)| max),
+                    paused: opts.paused,
+                    skip: opts.skip.clone(),
+                    last: opts.last.clone(),
+                },
+                RoutineInfo::Checkout { kind } => RoutineJson {
+                    kind_of: "checkout".into(),
+                    kind: kind.clone(),
+                    start_date: None,
+                    until: None,
+                    count: None,
+                    max: None,
+                    paused: false,
+                    skip: None,
+                    last: None,
+                },
+            }),
+            date_stamp: task.date_stamp.clone(),
+            tags: task.tags.clone(),
+            contexts: task.contexts.clone(),
+            priority: task.priority.map(String::from),
+            due: task.due.clone(),
+            tickler: task.tickler.clone(),
+        }
+    }
+
+    /// box info for json output, with pending(or all) tasks if needed
+    pub fn to_json(&mut self, listall: bool, with_tasks: bool) -> BoxJson {
+        let count = self.count();
+        BoxJson {
+            schema: JSON_SCHEMA_VERSION,
+            name: self.tbname.clone(),
+            alias: self.alias.clone().unwrap_or(get_box_alias(&self.tbname)),
+            encrypted: self.encrypted,
+            path: self.fpath.display().to_string(),
+            count: Some(count),
+            tasks: with_tasks.then(|| self.tasks.iter()
+                                       .filter(|t| listall || !t.is_done() && !self._is_deferred(t))
+                                       .map(|t| self._task_json(t, listall))
+                                       .collect()),
+        }
     }
 
-    pub fn mark(&mut self, items: Vec<String>, delete: bool) {
+    /// same as to_json(), with tasks filtered by #tags and @contexts
+    pub fn to_json_filtered(&mut self, listall: bool, filter: &TaskFilter) -> BoxJson {
+        let mut boxjson = self.to_json(listall, true);
+        if let (Some(shown), Some(tasks)) = (self._filtered(filter), boxjson.tasks.as_mut()) {
+            tasks.retain(|t| shown.contains(&t.id))
+        }
+        boxjson
+    }
+
+    /// occurrences of the pending routine rules in the days from the date, by date
+    pub fn occurrences(&mut self, from: NaiveDate, days: i64) -> Vec<OccurrenceJson> {
+        self.load();
+
+        let mut occurs = Vec::new();
+        for task in self.tasks.iter().filter(|t| !t.is_done()) {
+            let Some(caps) = RE_ROUTINES.captures(&task.text) else { continue };
+            let Ok(start_date) = NaiveDate::parse_from_str(&caps[2], "%Y-%m-%d") else { continue };
+            let opts = RoutineOpts::parse(&caps[3]);
+
+            // how many left by the max count
+            let mut left = opts.times.map_or(u32::MAX, |(n, max)| max.saturating_sub(n));
+            for date in from.iter_days().take(days.max(0) as usize) {
+                if left == 0 { break }
+
+                let date_str = date.to_string();
+                if opts.is_ended(&date_str) { break }
+                if ! util::match_routine_on(&caps[1], start_date, date) ||
+                   opts.is_paused(&date_str) || opts.skip.as_ref() == Some(&date_str) { continue }
+
+                left -= 1;
+                occurs.push(OccurrenceJson {
+                    date: date_str,
+                    id: task.id,
+                    kind: routine_kind_name(&caps[1]),
+                    text: caps[4].to_string(),
+                });
+            }
+        }
+        occurs.sort_by(|a, b| a.date.cmp(&b.date));
+        occurs
+    }
+
+    /// list tasks in json or ndjson(one task per line)
+    pub fn list_json(&mut self, listall: bool, format: OutputFormat, filter: &TaskFilter) {
+        let boxjson = self.to_json_filtered(listall, filter);
+        if format == OutputFormat::Ndjson {
+            for task in boxjson.tasks.iter().flatten() {
+                println!("{}", serde_json::to_string(&TaskLine {
+                    schema: boxjson.schema,
+                    name: &boxjson.name,
+                    alias: &boxjson.alias,
+                    task,
+                }).unwrap())
+            }
+        } else {
+            println!("{}", serde_json::to_string_pretty(&boxjson).unwrap())
+        }
+    }
+
+    pub fn count_json(&mut self, format: OutputFormat) {
+        let boxjson = self.to_json(false, false);
+        if format == OutputFormat::Ndjson {
+            println!("{}", serde_json::to_string(&boxjson).unwrap())
+        } else {
+            println!("{}", serde_json::to_string_pretty(&boxjson).unwrap())
+        }
+    }
+
+    pub fn mark(&mut self, items: Vec<usize>, delete: bool) {
+        self.mark_as(items, TaskStatus::Done, delete)
+    }
+
+    pub fn mark_as(&mut self, items: Vec<usize>, status: TaskStatus, delete: bool) {
         self.load();
 
         if items.is_empty() || self.tasks.is_empty() {
             return
         }
 
-        for (task, done) in self.tasks.iter_mut() {
-            if *done { continue }
-            if items.contains(task) {
-                *done = true;
+        for task in self.tasks.iter_mut() {
+            if task.is_done() { continue }
+            if items.contains(&task.id) {
+                task.status = status;
             }
         }
 
+        if matches!(status, TaskStatus::Done | TaskStatus::Cancelled) && self._policy() == SubtaskPolicy::All {
+            items.iter().for_each(|&id| self._close_parents(id))
+        }
+
         if delete {
-            self.tasks.retain(|(task, done)| !done || !items.contains(task))
+            items.iter().for_each(|&id| self._remove_task(id))
         }
 
         self._dump().unwrap()
@@ -490,35 +1744,41 @@ impl TaskBox {
         if self.tasks.is_empty() { return }
 
         // rules: to keep the original order,
-        // and when with same content:
+        // and when with same content under the same parent:
         //      done+done => done
-        //      not+not => not
+        //      not+not => not(the first one)
         //      done+not => not
+        // sub-tasks of the removed dups go to the kept one
 
-        let mut hs = HashSet::new();
-        let mut newtasks = Vec::new();
+        let mut kept :HashMap<(Option<usize>, String), usize> = HashMap::new();
+        let mut merged :HashMap<usize, usize> = HashMap::new();
+        let mut newtasks :Vec<Task> = Vec::new();
 
-        // 1st scan: remove dups
-        let mut tname;
-        for (task, done) in self.tasks.iter() {
-            tname = task.trim().to_string();
-            if ! hs.contains(&tname) {
-                newtasks.push((tname.clone(), *done));
-                hs.insert(tname);
-            }
-        }
-        // 2nd scan: check status
-        for (task, done) in newtasks.iter_mut() {
-            if *done && self.tasks.contains(&(task.to_string(), false)) {
-                *done = false
+        for task in self.tasks.iter() {
+            let parent = task.parent.map(|p| *merged.get(&p).unwrap_or(&p));
+            let key = (parent, task.text.trim().to_string());
+
+            if let Some(&kid) = kept.get(&key) {
+                merged.insert(task.id, kid);
+                if ! task.is_done() {
+                    if let Some(kt) = newtasks.iter_mut().find(|t| t.id == kid && t.is_done()) {
+                        kt.status = task.status
+                    }
+                }
+            } else {
+                let mut newtask = task.clone();
+                newtask.text = key.1.clone();
+                newtask.parent = parent;
+                kept.insert(key, task.id);
+                newtasks.push(newtask);
             }
         }
 
-        // (optional) 3rd scan: sort by completed and uncomplated
-        // upper: completed
-        if sort { newtasks.sort_by(|a, b| b.1.cmp(&a.1)) }
+        // (optional) sort tasks by completed and uncomplated, then by priority
+        // upper: completed, and sub-tasks are sorted within their parents
+        if sort { newtasks.sort_by_key(|t| (!t.is_done(), t.priority_rank())) }
 
-        self.tasks = newtasks;
+        self._set_tasks(newtasks);
         self._dump().unwrap()
     }
 
@@ -555,7 +1815,7 @@ impl TaskBox {
             let fpath = Path::new(&mdfile);
             if ! fpath.is_file() {
                 eprintln!("not a file or not exists: {}", S_fpath!(mdfile));
-                std::process::exit(1)
+                journal::exit(1)
             }
             println!("importing {} {}", S_fpath!(mdfile), PROGRESS);
 
@@ -623,13 +1883,13 @@ impl TaskBox {
 
         if zfile.len() != 1 {
             println!("Taskbox: {} is not a valid encrypted taskbox, skipped", S_checkbox!(tbname));
-            std::process::exit(1);
+            journal::exit(1);
         }
 
         let mut entry = zfile.by_index_decrypt(0, passwd.as_bytes())?;
         if entry.name() != tbname {
             println!("Taskbox: {} is not a valid encrypted taskbox, skipped", S_checkbox!(tbname));
-            std::process::exit(1);
+            journal::exit(1);
         }
 
         let mut content = String::new();
@@ -644,28 +1904,28 @@ impl TaskBox {
         // validating encryption status
         if self.encrypted {
             println!("Taskbox: {} was already encrypted, skipped", S_checkbox!(tbname));
-            std::process::exit(1);
+            journal::exit(1);
         }
 
-        // validating box name: reserved and date format box cannot enc
+        // validating box name: reserved and date boxes(of all levels) cannot enc
         let can_be = match tbname.as_ref() {
             ROUTINE_BOXNAME | INBOX_BOXNAME => false,
-            _ if Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap().is_match(&tbname) => false,
+            _ if box_period(&tbname).is_some() => false,
             _ => true
         };
         if ! can_be {
             println!("Taskbox: {} cannot be encrypted, skipped", S_checkbox!(tbname));
-            std::process::exit(1);
+            journal::exit(1);
         }
         if !self.fpath.exists() {
             println!("Taskbox: {} hasn't initialized, skipped", S_checkbox!(tbname));
-            std::process::exit(1);
+            journal::exit(1);
         }
 
         let passwd = i_getpass(true, None);
         if passwd.is_empty() {
             println!("password is empty, canceled");
-            std::process::exit(1);
+            journal::exit(1);
         }
 
         println!("Encrypting taskbox: {}", S_checkbox!(tbname));
@@ -677,6 +1937,9 @@ impl TaskBox {
         self._dump_with_passwd(&fs::read_to_string(&original_fpath)?, &passwd)?;
         fs::remove_file(&original_fpath)?;
 
+        // no plain content left in journal
+        journal::forget(Path::new(&Config_get!("basedir")), &original_fpath)?;
+
         Ok(())
     }
 
@@ -686,24 +1949,24 @@ impl TaskBox {
         // validating ext name
         if ! self.encrypted {
             println!("Taskbox: {} was not encrypted, skipped", S_checkbox!(tbname));
-            std::process::exit(1);
+            journal::exit(1);
         }
         if ! self.fpath.exists() {
             println!("Taskbox: {} hasn't initialized, skipped", S_checkbox!(tbname));
-            std::process::exit(1);
+            journal::exit(1);
         }
 
         let passwd = i_getpass(false, None);
         if passwd.is_empty() {
             println!("password is empty, canceled");
-            std::process::exit(1);
+            journal::exit(1);
         }
 
         println!("Decrypting taskbox: {}", S_checkbox!(tbname));
 
         let content = self._load_file_with_pass(&passwd).unwrap_or_else(|_| {
             println!("{}", S_failure!("wrong password, abort"));
-            std::process::exit(1);
+            journal::exit(1);
         });
         let original_fpath = self.fpath.clone();
 
@@ -44,67 +44,247 @@ pub fn pick_file(dir: &str) -> String {
     run_fun!(
         ls -r $dir | fzf -m --preview "cat '$dir/{}'";
     ).unwrap_or_else(|_|
-        std::process::exit(1)
+        journal::exit(1)
     )
 }
 
+thread_local! {
+    static NOW: std::cell::Cell<Option<NaiveDateTime>> = const { std::cell::Cell::new(None) };
+}
+
+/// override the clock for date logic in current thread, `None` to restore
+pub fn set_now(now: Option<NaiveDateTime>) {
+    NOW.set(now)
+}
+
+/// "YYYY-MM-DD HH:MM[:SS]", with ' ' or 'T', or a date alone as its noon
+pub fn parse_now(input: &str) -> Option<NaiveDateTime> {
+    let input = input.trim().replacen('T', " ", 1);
+    NaiveDateTime::parse_from_str(&input, "%Y-%m-%d %H:%M:%S").ok()
+        .or_else(|| NaiveDateTime::parse_from_str(&input, "%Y-%m-%d %H:%M").ok())
+        .or_else(|| NaiveDate::parse_from_str(&input, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(12, 0, 0)))
+}
+
+/// the wall clock time in config "timezone",
+/// overridden by `set_now()` or env var "TODOR_NOW"
+pub fn now() -> NaiveDateTime {
+    if let Some(now) = NOW.get() { return now }
+
+    if let Ok(input) = std::env::var("TODOR_NOW") {
+        return parse_now(&input).unwrap_or_else(|| {
+            println!("{} TODOR_NOW={}", S_failure!("invalid time:"), input);
+            journal::exit(1)
+        })
+    }
+
+    match config_tz() {
+        Some(tz) => Utc::now().with_timezone(&tz).naive_local(),
+        None => Local::now().naive_local(),
+    }
+}
+
+/// the clock of `now()` in UTC
+pub fn now_utc() -> DateTime<Utc> {
+    let now = now();
+    match config_tz() {
+        Some(tz) => tz.from_local_datetime(&now).earliest().map(|t| t.with_timezone(&Utc)),
+        None => Local.from_local_datetime(&now).earliest().map(|t| t.with_timezone(&Utc)),
+    }.unwrap_or_else(|| now.and_utc())
+}
+
+fn config_tz() -> Option<chrono_tz::Tz> {
+    CONFIG.read().unwrap().timezone.as_ref().and_then(|tz| tz.parse().ok())
+}
+
+/// the date of "today", by the clock and config "day_starts_at"
+pub fn today_date() -> NaiveDate {
+    let now = now();
+    date_of(now, CONFIG.read().unwrap().day_starts_at.unwrap_or(0))
+}
+
+// the hours after midnight still belong to the day before, till the day starts
+fn date_of(now: NaiveDateTime, day_starts_at: u32) -> NaiveDate {
+    (now - Duration::hours(day_starts_at.min(23) as i64)).date()
+}
+
 pub fn get_today() -> String {
-    Local::now().date_naive().to_string()
+    today_date().to_string()
 }
 pub fn get_yesterday() -> String {
-    Local::now().add(chrono::Duration::days(-1)).date_naive().to_string()
+    today_date().add(chrono::Duration::days(-1)).to_string()
 }
 pub fn get_tomorrow() -> String {
-    Local::now().add(chrono::Duration::days(1)).date_naive().to_string()
+    today_date().add(chrono::Duration::days(1)).to_string()
 }
 pub fn weekday_from_date(date_str: &str) -> String {
     if date_str.is_empty() { return "".into(); }
     NaiveDate::parse_from_str(date_str, "%Y-%m-%d").unwrap().weekday().to_string()
 }
 
+/// whether the routine rule(kind token and start date) happens on the date
+pub fn match_routine_on(kind: &str, start_date: NaiveDate, date: NaiveDate) -> bool {
+    let Ok(routine) = cli::Routine::parse(kind) else { return false };
+    if date < start_date { return false }
+
+    // the n-th step from the start date, clamped at month ends(e.g. 01-31 -> 02-29 -> 03-31)
+    let steps_on = |months: u32| {
+        (0..).map(|n| start_date + Months::new(n * months))
+             .find(|&d| d >= date) == Some(date)
+    };
+    let days = (date - start_date).num_days();
+
+    match routine {
+        // missed reminder will still show up
+        cli::Routine::Daily | cli::Routine::Once => true,
+        cli::Routine::Weekly => days % 7 == 0,
+        cli::Routine::Biweekly => days % 14 == 0,
+        cli::Routine::Qweekly => days % 28 == 0,
+        cli::Routine::EveryDays(n) => days % n as i64 == 0,
+        cli::Routine::Monthly => steps_on(1),
+        cli::Routine::Yearly => steps_on(12),
+        cli::Routine::Weekdays => date.weekday().num_days_from_monday() < 5,
+        cli::Routine::OnWeekdays(wds) => wds.contains(&date.weekday()),
+        cli::Routine::NthWeekday(n, wd) => date.weekday() == wd && (date.day() - 1) / 7 + 1 == n,
+        cli::Routine::LastWeekday(wd) => date.weekday() == wd && (date + Duration::days(7)).month() != date.month(),
+    }
+}
+
+/// match routine to the date, as "YYYY-MM-DD", "today", "next fri" etc.
 pub fn match_routine(kind: &str, start_date_str: &str, match_to: &str) -> bool {
-    let mut closest_date = NaiveDate::parse_from_str(start_date_str, "%Y-%m-%d").unwrap();
-    let match_to_date = match match_to {
-        "today" => Local::now().date_naive(),
-        "yesterday" => Local::now().add(chrono::Duration::days(-1)).date_naive(),
-        "tomorrow" => Local::now().add(chrono::Duration::days(1)).date_naive(),
-        _ => panic!("unsupported match_to date(only today/yesterday/tomorrow)"),
+    let Ok(start_date) = NaiveDate::parse_from_str(start_date_str, "%Y-%m-%d") else { return false };
+    let Some(match_to_date) = parse_date_from(match_to, today_date()) else { return false };
+
+    match_routine_on(kind, start_date, match_to_date)
+}
+
+/// the first date matching routine after the date, within about 4 years
+pub fn next_routine_date(kind: &str, start_date_str: &str, after: NaiveDate) -> Option<NaiveDate> {
+    let start_date = NaiveDate::parse_from_str(start_date_str, "%Y-%m-%d").ok()?;
+    after.iter_days().skip(1).take(1500).find(|&date| match_routine_on(kind, start_date, date))
+}
+
+fn date_offset(date: NaiveDate, n: i64, unit: &str) -> Option<NaiveDate> {
+    let months = |n: i64| if n >= 0 {
+        date.checked_add_months(Months::new(n as u32))
+    } else {
+        date.checked_sub_months(Months::new(n.unsigned_abs() as u32))
     };
 
-    if kind == "m" {
-        while closest_date < match_to_date {
-            closest_date = closest_date + chrono::Months::new(1);
+    match unit {
+        "d" | "day" | "days" => date.checked_add_signed(Duration::days(n)),
+        "w" | "week" | "weeks" => date.checked_add_signed(Duration::weeks(n)),
+        "m" | "month" | "months" => months(n),
+        "y" | "year" | "years" => months(n * 12),
+        _ => None,
+    }
+}
+
+// the first date matching weekday, from the date(including it or not)
+fn next_weekday(date: NaiveDate, weekday: Weekday, including: bool) -> NaiveDate {
+    let start = if including { date } else { date + Duration::days(1) };
+    let days = (7 + weekday.num_days_from_monday() - start.weekday().num_days_from_monday()) % 7;
+    start + Duration::days(days as i64)
+}
+
+/// date in natural language relative to today, e.g. "mon", "next friday", "+3d",
+/// "in 2 weeks", "end of month", besides "YYYY-MM-DD"
+pub fn parse_date_from(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let input = input.trim().to_lowercase();
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        return Some(date)
+    }
+
+    let words :Vec<&str> = input.split_whitespace().collect();
+    match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] | ["tomo"] => today.succ_opt(),
+        ["yesterday"] => today.pred_opt(),
+        ["end", "of", "week"] | ["eow"] => Some(next_weekday(today, Weekday::Sun, true)),
+        ["end", "of", "month"] | ["eom"] =>
+            date_offset(today.with_day(1)?, 1, "m")?.pred_opt(),
+        ["next", "week"] => Some(next_weekday(today, Weekday::Mon, false)),
+        ["next", "month"] => date_offset(today.with_day(1)?, 1, "m"),
+        ["next", weekday] => weekday.parse().ok().map(|wd| next_weekday(today, wd, false)),
+        ["this", weekday] | [weekday] if weekday.parse::<Weekday>().is_ok() =>
+            Some(next_weekday(today, weekday.parse().unwrap(), true)),
+        ["in", n, unit] => {
+            let n = if *n == "a" || *n == "an" { 1 } else { n.parse().ok()? };
+            date_offset(today, n, unit)
         }
-    } else {
-        let steps = match kind {
-            "d" | "1" => 1,
-            "w" => 7,
-            "b" => 14,
-            "q" => 28,
-            _ => panic!("unknown routine kind"),
-        };
-        while closest_date < match_to_date {
-            closest_date += chrono::Duration::days(steps);
+        [offset] if offset.starts_with(['+', '-']) => {
+            let unit_at = offset.find(|c: char| c.is_ascii_alphabetic())?;
+            date_offset(today, offset[..unit_at].parse().ok()?, &offset[unit_at..])
         }
+        _ => None,
     }
+}
+
+/// same as parse_date_from() with today, as "YYYY-MM-DD"
+pub fn parse_date(input: &str) -> Option<String> {
+    parse_date_from(input, today_date()).map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+/// dates of range "FROM..TO" in natural language, e.g. "mon..fri", at most one year
+pub fn parse_date_range(input: &str) -> Option<Vec<String>> {
+    let (from, to) = input.split_once("..")?;
+    let from = parse_date_from(from, today_date())?;
+    let to = parse_date_from(to, from)?;
+    if to < from || to > from + Duration::days(366) { return None }
+
+    Some(from.iter_days().take_while(|&d| d <= to).map(|d| d.format("%Y-%m-%d").to_string()).collect())
+}
+
+/// daily box named as "YYYY-MM-DD"
+pub fn is_date_box(boxname: &str) -> bool {
+    NaiveDate::parse_from_str(boxname, "%Y-%m-%d").is_ok()
+}
+
+/// name of the date box of the level, which the date is in
+pub fn period_box_name(level: cli::BoxLevel, date: NaiveDate) -> String {
+    match level {
+        cli::BoxLevel::Day => date.format("%Y-%m-%d"),
+        cli::BoxLevel::Week => date.format("%G-W%V"),
+        cli::BoxLevel::Month => date.format("%Y-%m"),
+    }.to_string()
+}
 
-    closest_date == match_to_date
+/// level and the first/last dates of date boxes, None for other boxes
+pub fn box_period(boxname: &str) -> Option<(cli::BoxLevel, NaiveDate, NaiveDate)> {
+    if let Ok(date) = NaiveDate::parse_from_str(boxname, "%Y-%m-%d") {
+        Some((cli::BoxLevel::Day, date, date))
+    } else if boxname.len() == 8 && boxname.get(4..6) == Some("-W") {
+        let first = NaiveDate::parse_from_str(&format!("{}-1", boxname), "%G-W%V-%u").ok()?;
+        Some((cli::BoxLevel::Week, first, first + Duration::days(6)))
+    } else if boxname.len() == 7 {
+        let first = NaiveDate::parse_from_str(&format!("{}-01", boxname), "%Y-%m-%d").ok()?;
+        Some((cli::BoxLevel::Month, first, date_offset(first, 1, "m")?.pred_opt()?))
+    } else {
+        None
+    }
 }
 
 pub fn get_box_alias(name_in: &str) -> String {
+    let today = today_date();
     match name_in {
         _ if name_in == get_today() => "today",
         _ if name_in == get_tomorrow() => "tomorrow",
         _ if name_in == get_yesterday() => "yesterday",
+        _ if name_in == period_box_name(cli::BoxLevel::Week, today) => "thisweek",
+        _ if name_in == period_box_name(cli::BoxLevel::Week, today + Duration::weeks(1)) => "nextweek",
+        _ if name_in == period_box_name(cli::BoxLevel::Month, today) => "thismonth",
         _ => name_in,
     }.into()
 }
 
 pub fn get_box_unalias(alias: &str) -> String {
+    let today = today_date();
     match alias {
         "today" => get_today(),
         "yesterday" => get_yesterday(),
         "tomorrow" => get_tomorrow(),
+        "thisweek" => period_box_name(cli::BoxLevel::Week, today),
+        "nextweek" => period_box_name(cli::BoxLevel::Week, today + Duration::weeks(1)),
+        "thismonth" => period_box_name(cli::BoxLevel::Month, today),
         "inbox" => taskbox::INBOX_BOXNAME.into(),
         "routine" | "routines" => taskbox::ROUTINE_BOXNAME.into(),
         _ => alias.into(),
@@ -115,8 +295,27 @@ pub fn get_inbox_file(inbox: &str) -> PathBuf {
     let basedir = PathBuf::from(Config_get!("basedir"));
     let enc_box = basedir.join(inbox).with_extension("mdx");
 
-    if enc_box.exists() { enc_box }
-    else { basedir.join(get_box_unalias(inbox)).with_extension("md") }
+    if enc_box.exists() { return enc_box }
+
+    // existing named box first, and then the daily box of natural language date
+    let named_box = basedir.join(get_box_unalias(inbox)).with_extension("md");
+    if named_box.exists() { return named_box }
+
+    // only the explicit ones, e.g. "next mon", "in 3 days", "+3d",
+    // to keep single words like "mon", "eow" for named boxes
+    let explicit = inbox.contains(char::is_whitespace) || inbox.starts_with(['+', '-']);
+    match parse_date(inbox).filter(|_| explicit) {
+        Some(date) => basedir.join(date).with_extension("md"),
+        None => named_box,
+    }
+}
+
+// all chars of pattern appear in text in order, ignoring case
+pub fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut chars = text.chars().flat_map(char::to_lowercase);
+    pattern.chars().flat_map(char::to_lowercase)
+           .filter(|c| !c.is_whitespace())
+           .all(|p| chars.any(|c| c == p))
 }
 
 // following i_* fn are for "inquire" based wrappers
@@ -155,26 +354,34 @@ pub fn i_gettext() -> String {
     input.trim().to_string()
 }
 
-pub fn i_select(tasks: Vec<String>, title: &str) -> Vec<String> {
+pub fn i_select<T: std::fmt::Display>(tasks: Vec<T>, title: &str) -> Vec<T> {
     execute!(std::io::stdout(), BlinkingBlock).expect("failed to set cursor");
     let mut selected = inquire::MultiSelect::new(title, tasks)
         .with_render_config(get_multi_select_style())
         .with_vim_mode(true)
         .with_page_size(10)
         .with_help_message("h/j/k/l | ←↑↓→ | <space> | <enter> | ctrl+c")
-        .prompt().unwrap_or_else(|_| std::process::exit(1));
+        .prompt().unwrap_or_else(|_| journal::exit(1));
     execute!(std::io::stdout(), DefaultUserShape).expect("failed to set cursor");
-    selected.retain(|x| !x.contains(WARN));
+    selected.retain(|x| !x.to_string().contains(WARN));
     selected
 }
 
+pub fn i_choose<T: std::fmt::Display>(options: Vec<T>, title: &str) -> T {
+    inquire::Select::new(title, options)
+        .with_render_config(get_multi_select_style())
+        .with_vim_mode(true)
+        .with_help_message("j/k | ↑↓ | <enter> | ctrl+c")
+        .prompt().unwrap_or_else(|_| journal::exit(1))
+}
+
 pub fn i_getdate(routine_kind: &str) -> String {
     inquire::DateSelect::new(&format!(" {} from:",S_routine!(routine_kind)))
         .with_render_config(get_date_input_style())
         .with_help_message("h/j/k/l | <enter> | ctrl+c")
         .prompt().unwrap_or_else(|_| {
                 println!("{}", S_empty!("No starting date selected, skip."));
-                std::process::exit(1)
+                journal::exit(1)
             }).to_string()
 }
 
@@ -197,6 +404,170 @@ mod tests {
         assert_eq!(get_box_unalias("yesterday"), get_yesterday());
         assert_eq!(get_box_unalias("tomorrow"), get_tomorrow());
         assert_eq!(get_box_unalias("dummy"), "dummy".to_string());
+
+        let thisweek = get_box_unalias("thisweek");
+        assert_eq!(get_box_alias(&thisweek), "thisweek");
+        assert_eq!(get_box_alias(&get_box_unalias("nextweek")), "nextweek");
+        assert_eq!(get_box_alias(&get_box_unalias("thismonth")), "thismonth");
+        let (_, first, last) = box_period(&thisweek).unwrap();
+        assert!(first.to_string() <= get_today() && get_today() <= last.to_string());
+    }
+
+    #[test]
+    fn test_date_of() {
+        let now = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        assert_eq!(date_of(now("2026-10-15 00:30"), 0), date("2026-10-15"));
+        assert_eq!(date_of(now("2026-10-15 00:30"), 4), date("2026-10-14"));
+        assert_eq!(date_of(now("2026-10-15 04:00"), 4), date("2026-10-15"));
+        assert_eq!(date_of(now("2026-03-01 03:59"), 4), date("2026-02-28"));
+    }
+
+    #[test]
+    fn test_inbox_file_of_date() {
+        let stem = |name| get_inbox_file(name).file_stem().unwrap().to_str().unwrap().to_string();
+
+        assert_eq!(stem("next mon"), parse_date("next mon").unwrap());
+        assert_eq!(stem("in 3 days"), parse_date("+3d").unwrap());
+        assert_eq!(stem("+3d"), parse_date("+3d").unwrap());
+        assert_eq!(stem("mon"), "mon");
+        assert_eq!(stem("eow"), "eow");
+        assert_eq!(stem("tomorrow"), get_tomorrow());
+    }
+
+    #[test]
+    fn test_parse_now() {
+        let now = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+
+        assert_eq!(parse_now("2028-02-29 23:59:59"), Some(now("2028-02-29 23:59:59")));
+        assert_eq!(parse_now("2028-02-29T08:30"), Some(now("2028-02-29 08:30:00")));
+        assert_eq!(parse_now("2028-02-29"), Some(now("2028-02-29 12:00:00")));
+        assert_eq!(parse_now("2027-02-29"), None);
+        assert_eq!(parse_now("tomorrow"), None);
+    }
+
+    #[test]
+    fn test_box_period() {
+        use cli::BoxLevel;
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        assert_eq!(box_period("2026-10-14"), Some((BoxLevel::Day, date("2026-10-14"), date("2026-10-14"))));
+        assert_eq!(box_period("2026-W42"), Some((BoxLevel::Week, date("2026-10-12"), date("2026-10-18"))));
+        assert_eq!(box_period("2027-W01"), Some((BoxLevel::Week, date("2027-01-04"), date("2027-01-10"))));
+        assert_eq!(box_period("2026-02"), Some((BoxLevel::Month, date("2026-02-01"), date("2026-02-28"))));
+        assert_eq!(box_period("2026-12"), Some((BoxLevel::Month, date("2026-12-01"), date("2026-12-31"))));
+        assert_eq!(box_period("2026-W54"), None);
+        assert_eq!(box_period("2026-13"), None);
+        assert_eq!(box_period("INBOX"), None);
+
+        assert_eq!(period_box_name(BoxLevel::Week, date("2027-01-01")), "2026-W53");
+        assert_eq!(period_box_name(BoxLevel::Month, date("2027-01-01")), "2027-01");
+    }
+
+    #[test]
+    fn test_routine_parse() {
+        use cli::Routine;
+        use chrono::Weekday::*;
+
+        let cases = [
+            ("daily", Routine::Daily, "d", "daily"),
+            ("D", Routine::Daily, "d", "daily"),
+            ("once", Routine::Once, "1", "reminder"),
+            ("yearly", Routine::Yearly, "y", "yearly"),
+            ("weekdays", Routine::Weekdays, "wd", "weekdays"),
+            ("every 3 days", Routine::EveryDays(3), "3d", "every-3-days"),
+            ("every 2 weeks", Routine::EveryDays(14), "14d", "every-14-days"),
+            ("Mon/Wed/Fri", Routine::OnWeekdays(vec![Mon, Wed, Fri]), "mon,wed,fri", "mon/wed/fri"),
+            ("2nd tuesday", Routine::NthWeekday(2, Tue), "2tue", "2nd-tue"),
+            ("last fri", Routine::LastWeekday(Fri), "lastfri", "last-fri"),
+        ];
+        for (input, routine, token, name) in cases {
+            assert_eq!(Routine::parse(input), Ok(routine.clone()));
+            assert_eq!(routine.token(), token);
+            assert_eq!(routine.name(), name);
+            assert_eq!(Routine::parse(token), Ok(routine));
+        }
+
+        assert!(Routine::parse("every 0 days").is_err());
+        assert!(Routine::parse("6th mon").is_err());
+        assert!(Routine::parse("sometimes").is_err());
+    }
+
+    #[test]
+    fn test_match_routine_on() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let start = date("2026-10-01"); // Thu
+
+        assert!(match_routine_on("d", start, date("2026-10-05")));
+        assert!(!match_routine_on("d", start, date("2026-09-30")));
+        assert!(match_routine_on("w", start, date("2026-10-15")));
+        assert!(!match_routine_on("b", start, date("2026-10-08")));
+        assert!(match_routine_on("m", start, date("2026-11-01")));
+        assert!(match_routine_on("3d", start, date("2026-10-07")));
+        assert!(!match_routine_on("3d", start, date("2026-10-08")));
+        assert!(match_routine_on("wd", start, date("2026-10-16")));
+        assert!(!match_routine_on("wd", start, date("2026-10-17")));
+        assert!(match_routine_on("mon,wed,fri", start, date("2026-10-14")));
+        assert!(!match_routine_on("mon,wed,fri", start, date("2026-10-15")));
+        assert!(match_routine_on("2tue", start, date("2026-10-13")));
+        assert!(!match_routine_on("2tue", start, date("2026-10-20")));
+        assert!(match_routine_on("lastfri", start, date("2026-10-30")));
+        assert!(!match_routine_on("lastfri", start, date("2026-10-23")));
+        assert!(match_routine_on("y", start, date("2027-10-01")));
+        assert!(!match_routine_on("y", start, date("2026-11-01")));
+        assert!(!match_routine_on("unknown", start, date("2026-10-01")));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap(); // Wed
+        let date = |s: &str| parse_date_from(s, today).map(|d| d.format("%Y-%m-%d").to_string());
+
+        assert_eq!(date("2026-11-01"), Some("2026-11-01".into()));
+        assert_eq!(date("today"), Some("2026-10-14".into()));
+        assert_eq!(date("tomorrow"), Some("2026-10-15".into()));
+        assert_eq!(date("mon"), Some("2026-10-19".into()));
+        assert_eq!(date("Wed"), Some("2026-10-14".into()));
+        assert_eq!(date("next wednesday"), Some("2026-10-21".into()));
+        assert_eq!(date("next friday"), Some("2026-10-16".into()));
+        assert_eq!(date("+3d"), Some("2026-10-17".into()));
+        assert_eq!(date("-1w"), Some("2026-10-07".into()));
+        assert_eq!(date("+1m"), Some("2026-11-14".into()));
+        assert_eq!(date("in 2 weeks"), Some("2026-10-28".into()));
+        assert_eq!(date("in a month"), Some("2026-11-14".into()));
+        assert_eq!(date("end of month"), Some("2026-10-31".into()));
+        assert_eq!(date("end of week"), Some("2026-10-18".into()));
+        assert_eq!(date("next week"), Some("2026-10-19".into()));
+        assert_eq!(date("next month"), Some("2026-11-01".into()));
+        assert_eq!(date("2026-13-01"), None);
+        assert_eq!(date("inbox"), None);
+        assert_eq!(date("+3x"), None);
+    }
+
+    #[test]
+    fn test_parse_date_range() {
+        let dates = parse_date_range("2026-10-19..fri").unwrap();
+        assert_eq!(dates.len(), 5);
+        assert_eq!(dates[4], "2026-10-23");
+        assert_eq!(parse_date_range("2026-10-19..+2d").unwrap().len(), 3);
+        assert_eq!(parse_date_range("2026-10-19..2026-10-18"), None);
+        assert_eq!(parse_date_range("2026-10-19..2028-01-01"), None);
+        assert_eq!(parse_date_range("mon"), None);
+
+        assert!(match_routine("d", "2026-10-01", "2026-10-23"));
+        assert!(match_routine("w", "2026-10-01", "2026-10-08"));
+        assert!(!match_routine("w", "2026-10-01", "2026-10-09"));
+        assert!(!match_routine("w", "2026-10-01", "someday"));
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("bmk", "buy milk"));
+        assert!(fuzzy_match("Buy MILK", "buy some milk"));
+        assert!(fuzzy_match("", "anything"));
+        assert!(!fuzzy_match("mb", "buy milk"));
+        assert!(!fuzzy_match("milks", "buy milk"));
     }
 
     #[test]
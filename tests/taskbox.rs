@@ -20,6 +20,40 @@ fn setup_test_taskbox(name: &str) -> (TaskBox, tempfile::TempDir) {
     (TaskBox::new(file_path), dir)
 }
 
+// a basedir with config for the todor binary, and the runner of it(at the time if given) returning stdout
+fn setup_test_cli() -> (tempfile::TempDir, impl Fn(Option<&str>, &[&str]) -> String) {
+    let dir = tempdir().unwrap();
+    let conf = dir.path().join("config.toml");
+    fs::write(&conf, format!("basedir = \"{}\"\nblink = false\n", dir.path().display())).expect("write err");
+
+    let todor = move |now: Option<&str>, args: &[&str]| {
+        let mut cmd = std::process::Command::new(env!("CARGO_BIN_EXE_todor"));
+        match now {
+            Some(now) => cmd.env("TODOR_NOW", now),
+            None => cmd.env_remove("TODOR_NOW"),
+        };
+        let output = cmd.arg("-c").arg(&conf).args(args).output().unwrap();
+        assert!(output.status.success(), "todor {:?} failed", args);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    (dir, todor)
+}
+
+// per-box config with the box names of the test only, as CONFIG is shared by the tests in parallel
+fn set_box_config(boxname: &str, boxconf: BoxConfig) {
+    let mut g_conf = CONFIG.write().unwrap();
+    g_conf.boxes.get_or_insert_with(Default::default).insert(boxname.into(), boxconf);
+}
+
+fn unset_box_config(boxname: &str) {
+    let mut g_conf = CONFIG.write().unwrap();
+    if let Some(boxes) = g_conf.boxes.as_mut() { boxes.remove(boxname); }
+}
+
+fn find_ids(tb: &TaskBox, texts: &[&str]) -> Vec<usize> {
+    tb.tasks.iter().filter(|t| texts.contains(&t.text.as_str())).map(|t| t.id).collect()
+}
+
 #[test]
 fn test_taskbox_new() {
     let (tb, _dir) = setup_test_taskbox("test");
@@ -36,8 +70,8 @@ fn test_add_and_list() {
 
     tb.load();
     assert_eq!(tb.tasks.len(), 2);
-    assert!(tb.tasks.contains(&("Test task".to_string(), false)));
-    assert!(tb.tasks.iter().any(|(task, _)| task.starts_with("Test task with date")));
+    assert!(tb.tasks.iter().any(|t| t.text == "Test task" && !t.is_done()));
+    assert!(tb.tasks.iter().any(|t| t.text.starts_with("Test task with date")));
 }
 
 #[test]
@@ -47,10 +81,10 @@ fn test_mark() {
     tb.add("Task 2".to_string(), None, false, "");
     tb.add("Task 3".to_string(), None, false, "");
 
-    tb.mark(vec!["Task 1".to_string(), "Task 3".to_string()], false);
+    tb.mark(find_ids(&tb, &["Task 1", "Task 3"]), false);
     tb.load();
-    assert_eq!(tb.tasks.iter().filter(|(_, done)| *done).count(), 2);
-    assert_eq!(tb.tasks.iter().filter(|(_, done)| !done).count(), 1);
+    assert_eq!(tb.tasks.iter().filter(|t| t.is_done()).count(), 2);
+    assert_eq!(tb.tasks.iter().filter(|t| !t.is_done()).count(), 1);
 }
 
 #[test]
@@ -60,10 +94,10 @@ fn test_mark_and_delete() {
     tb.add("Task 2".to_string(), None, false, "");
     tb.add("Task 3".to_string(), None, false, "");
 
-    tb.mark(vec!["Task 1".to_string(), "Task 3".to_string()], true);
+    tb.mark(find_ids(&tb, &["Task 1", "Task 3"]), true);
     tb.load();
-    assert_eq!(tb.tasks.iter().filter(|(_, done)| *done).count(), 0);
-    assert_eq!(tb.tasks.iter().filter(|(_, done)| !done).count(), 1);
+    assert_eq!(tb.tasks.iter().filter(|t| t.is_done()).count(), 0);
+    assert_eq!(tb.tasks.iter().filter(|t| !t.is_done()).count(), 1);
 }
 
 #[test]
@@ -98,12 +132,12 @@ fn test_collect_from_basic() {
 
     tb2.load();
     assert_eq!(tb2.tasks.len(), 2);
-    assert_eq!(tb2.tasks[0].0, "Task to move");
-    assert_eq!(tb2.tasks[1].0, "Task2 to move");
+    assert_eq!(tb2.tasks[0].text, "Task to move");
+    assert_eq!(tb2.tasks[1].text, "Task2 to move");
 
     tb1.load();
     assert_eq!(tb1.tasks.len(), 1);
-    assert_eq!(tb1.tasks[0].0, "Task not to move");
+    assert_eq!(tb1.tasks[0].text, "Task not to move");
 }
 
 #[test]
@@ -120,9 +154,9 @@ fn test_collect_from_with_warn_msg() {
     tb2.collect_from(&mut tb1);
     tb2.load();
     assert_eq!(tb2.tasks.len(), 2);
-    assert_eq!(tb2.tasks[0].0, "Task to move");
-    assert!(tb2.tasks[1].0.starts_with("{󰃯:d "));
-    assert!(tb2.tasks[1].0.ends_with("} Daily routine"));
+    assert_eq!(tb2.tasks[0].text, "Task to move");
+    assert!(tb2.tasks[1].text.starts_with("{󰃯:d "));
+    assert!(tb2.tasks[1].text.ends_with("} Daily routine"));
 
     tb1.load();
     assert_eq!(tb1.tasks.len(), 0);
@@ -279,8 +313,8 @@ fn test_add_routine() {
 
     tb.load();
     assert_eq!(tb.tasks.len(), 1);
-    assert!(tb.tasks[0].0.starts_with("{󰃯:d "));
-    assert!(tb.tasks[0].0.ends_with("} Daily routine"));
+    assert!(tb.tasks[0].text.starts_with("{󰃯:d "));
+    assert!(tb.tasks[0].text.ends_with("} Daily routine"));
 }
 
 #[test]
@@ -299,24 +333,24 @@ fn test_checkout() {
 
     routine.load();
     assert_eq!(routine.tasks.len(), 4);
-    assert!(routine.tasks[0].0.starts_with("{󰃯:d "));
-    assert!(routine.tasks[0].0.ends_with("} Daily routine"));
-    assert!(routine.tasks[1].0.starts_with("{󰃯:1 "));
-    assert!(routine.tasks[1].0.ends_with("} reminder today"));
-    assert!(routine.tasks[2].0.starts_with("{󰃯:1 "));
-    assert!(routine.tasks[2].0.ends_with("} reminder tomorrow"));
+    assert!(routine.tasks[0].text.starts_with("{󰃯:d "));
+    assert!(routine.tasks[0].text.ends_with("} Daily routine"));
+    assert!(routine.tasks[1].text.starts_with("{󰃯:1 "));
+    assert!(routine.tasks[1].text.ends_with("} reminder today"));
+    assert!(routine.tasks[2].text.starts_with("{󰃯:1 "));
+    assert!(routine.tasks[2].text.ends_with("} reminder tomorrow"));
 
     today.collect_from(&mut routine);
 
     today.load();
     assert_eq!(today.tasks.len(), 2);
-    assert!(today.tasks[0].0.starts_with("{󰃯:daily} "));
-    assert!(today.tasks[0].0.contains("} Daily routine"));
-    assert!(today.tasks[0].0.contains(" [󰴹 "));
+    assert!(today.tasks[0].text.starts_with("{󰃯:daily} "));
+    assert!(today.tasks[0].text.contains("} Daily routine"));
+    assert!(today.tasks[0].text.contains(" [󰴹 "));
 
-    assert!(today.tasks[1].0.starts_with("{󰃯:reminder} "));
-    assert!(today.tasks[1].0.contains("} reminder today"));
-    assert!(today.tasks[1].0.contains(" [󰴹 "));
+    assert!(today.tasks[1].text.starts_with("{󰃯:reminder} "));
+    assert!(today.tasks[1].text.contains("} reminder today"));
+    assert!(today.tasks[1].text.contains(" [󰴹 "));
 
     tb.collect_from(&mut routine);
     tb.load();
@@ -326,6 +360,105 @@ fn test_checkout() {
     assert_eq!(routine.tasks.len(), 3);
 }
 
+#[test]
+fn test_checkout_coalesce() {
+    let (mut tomorrow, _dir) = setup_test_taskbox(&get_tomorrow());
+    let mut routine = tomorrow.sibling("routine");
+
+    fs::write(&tomorrow.fpath, format!(r#"# {}
+
+- [ ] {{󰃯:daily}} Daily routine [󰴹 {}]
+- [x] {{󰃯:daily}} Daily routine [󰴹 {}]
+- [ ] {{󰃯:weekly}} Daily routine [󰴹 {}]
+"#, get_tomorrow(), get_today(), get_today(), get_today())).expect("write err");
+    routine.add("Daily routine".to_string(), Some(Routine::Daily), false, &get_today());
+
+    // the unfinished one from today
+    tomorrow.collect_from(&mut routine);
+    tomorrow.load();
+    assert_eq!(tomorrow.tasks.len(), 3);
+    assert!(tomorrow.tasks[0].is_done());
+    assert_eq!(tomorrow.tasks[1].text, format!("{{󰃯:weekly}} Daily routine [󰴹 {}]", get_today()));
+    assert_eq!(tomorrow.tasks[2].text,
+               format!("{{󰃯:daily}} Daily routine (missed 1 time) [󰴹 {}]", get_tomorrow()));
+
+    // counted up by the sinking ones
+    let old_path = tomorrow.sibling("2000-01-01").fpath;
+    fs::write(&old_path, "- [ ] {󰃯:daily} Daily routine (missed 2 times) [󰴹 2000-01-01]\n").expect("write err");
+    tomorrow.collect_from(&mut TaskBox::new(old_path));
+    tomorrow.load();
+    assert_eq!(tomorrow.tasks.len(), 3);
+    assert_eq!(tomorrow.tasks[2].text,
+               format!("{{󰃯:daily}} Daily routine (missed 4 times) [󰴹 {}]", get_tomorrow()));
+
+    // only the newest one kept
+    set_box_config("2999-01-02", BoxConfig { routine_coalesce: Some(RoutineCoalesce::Newest), ..Default::default() });
+
+    let (from_path, to_path) = (tomorrow.sibling("2999-01-01").fpath, tomorrow.sibling("2999-01-02").fpath);
+    fs::write(&from_path, "- [ ] {󰃯:daily} task [󰴹 2999-01-01]\n").expect("write err");
+    fs::write(&to_path, "- [ ] {󰃯:daily} task [󰴹 2999-01-02]\n").expect("write err");
+    let mut to = TaskBox::new(to_path);
+    to.collect_from(&mut TaskBox::new(from_path));
+    to.load();
+    assert_eq!(to.tasks.len(), 1);
+    assert_eq!(to.tasks[0].text, "{󰃯:daily} task [󰴹 2999-01-02]");
+    unset_box_config("2999-01-02");
+}
+
+#[test]
+fn test_checkout_future_dates() {
+    let after = |n| (today_date() + chrono::Duration::days(n)).to_string();
+    let (tb, _dir) = setup_test_taskbox("test");
+    let mut routine = tb.sibling("routine");
+
+    routine.add_routine("daily".to_string(), Routine::Daily, &get_today(),
+                        &RoutineOpts { skip: Some(after(4)), ..Default::default() });
+    routine.add("weekly".to_string(), Some(Routine::Weekly), false, &get_today());
+
+    // pre-filled only once
+    for _ in 0..2 {
+        let mut future = tb.sibling(&after(7));
+        future.collect_from(&mut routine);
+    }
+    let future = tb.sibling(&after(7));
+    assert_eq!(future.tasks.len(), 2);
+    assert_eq!(future.tasks[1].text, format!("{{󰃯:weekly}} weekly [󰴹 {}]", after(7)));
+
+    // the skipped one before it is kept
+    let mut routine = tb.sibling("routine");
+    let Some(RoutineInfo::Rule { opts, .. }) = &routine.tasks[0].routine else { panic!() };
+    assert_eq!(opts, &RoutineOpts { skip: Some(after(4)), ..Default::default() });
+
+    let mut skipped = tb.sibling(&after(4));
+    skipped.collect_from(&mut routine);
+    skipped.load();
+    assert_eq!(skipped.tasks.len(), 0);
+
+    // only used up by the checkout on the day itself
+    let routine = tb.sibling("routine");
+    let Some(RoutineInfo::Rule { opts, .. }) = &routine.tasks[0].routine else { panic!() };
+    assert_eq!(opts, &RoutineOpts { skip: Some(after(4)), ..Default::default() });
+}
+
+#[test]
+fn test_schedule() {
+    let (mut inbox, _dir) = setup_test_taskbox("INBOX");
+    inbox.add("buy milk".to_string(), None, false, "");
+    inbox.add("call mom".to_string(), None, false, "");
+
+    let date = parse_date("in 3 days").unwrap();
+    let mut future = inbox.sibling(&date);
+    inbox.selected = Some(inbox.select(&TaskSelector::parse("/milk/").unwrap()));
+    future.collect_from(&mut inbox);
+
+    future.load();
+    assert_eq!(future.tasks.len(), 1);
+    assert_eq!(future.tasks[0].text, "buy milk");
+    inbox.load();
+    assert_eq!(inbox.tasks.len(), 1);
+    assert_eq!(inbox.tasks[0].text, "call mom");
+}
+
 #[test]
 fn test_pool_today_to_inbox() {
     let today_input = format!(r#"# {}
@@ -405,3 +538,1031 @@ fn test_import_somefile_to_inbox() {
     inbox.import(Some(fpath.to_str().unwrap().to_string()), true);
     assert_eq!(inbox.tasks.len(), 6);
 }
+
+#[test]
+fn test_load_task_tree_and_meta() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+
+    let test_input = r#"# test
+
+- [ ] Task one #work
+  - [x] SubTask1 of one
+  - [ ] SubTask2 of one
+- [ ] Task two [󰴹 2024-10-01]
+- [ ] {󰃯:daily} Daily routine [󰴹 2024-10-02]
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+    tb.load();
+
+    assert_eq!(tb.tasks.len(), 5);
+    assert_eq!(tb.tasks[0].children, vec![1, 2]);
+    assert_eq!(tb.tasks[1].parent, Some(0));
+    assert_eq!(tb.tasks[1].status, TaskStatus::Done);
+    assert_eq!(tb.tasks[0].tags, vec!["work".to_string()]);
+    assert_eq!(tb.tasks[3].date_stamp, Some("2024-10-01".into()));
+    assert_eq!(tb.tasks[4].routine, Some(RoutineInfo::Checkout { kind: "daily".into() }));
+
+    let items = tb.get_all_to_mark();
+    assert_eq!(items.len(), 4);
+    assert!(items[1].label.ends_with("SubTask2 of one"));
+}
+
+#[test]
+fn test_purge_with_sub() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+
+    let test_input = r#"# test
+
+- [x] Task one
+  - [ ] SubTask1
+- [ ] Task two
+- [ ] Task one
+  - [x] SubTask1
+  - [ ] SubTask2
+"#;
+    let test_output = r#"# test
+
+- [ ] Task one
+  - [ ] SubTask1
+  - [ ] SubTask2
+- [ ] Task two
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+    tb.purge(false);
+
+    let test_actual = fs::read_to_string(&tb.fpath).expect("Failed to read file");
+    assert_eq!(test_output, test_actual);
+}
+
+#[test]
+fn test_lossless_with_non_task_content() {
+    let (mut tb1, _dir) = setup_test_taskbox("test1");
+    let mut tb2 = tb1.sibling("test2");
+
+    let test1_input = r#"---
+tags: [ todo ]
+---
+# test1
+
+Some notes about this box.
+
+## daily
+- [ ] Task one   
+- [X] Task done
+  * not a task
+- [ ] Task to move
+
+```
+- [ ] not a task in code block
+```
+
+## weekly
+- [ ] Task weekly
+"#;
+    let test1_output = r#"---
+tags: [ todo ]
+---
+# test1
+
+Some notes about this box.
+
+## daily
+- [x] Task one
+- [X] Task done
+  * not a task
+
+```
+- [ ] not a task in code block
+```
+
+## weekly
+- [ ] Task weekly
+"#;
+
+    fs::write(&tb1.fpath, test1_input).expect("Failed to write test input to file");
+    tb1.load();
+    assert_eq!(tb1.tasks.len(), 4);
+    assert_eq!(tb1.alias, Some("test1".into()));
+
+    // nothing touched
+    tb1.mark(vec![], false);
+    tb1.purge(false);
+    let test1_actual = fs::read_to_string(&tb1.fpath).expect("Failed to read tb1 file");
+    assert_eq!(test1_input, test1_actual);
+
+    tb1.mark(find_ids(&tb1, &["Task one"]), false);
+    tb1.selected = Some(find_ids(&tb1, &["Task to move"]));
+    tb2.collect_from(&mut tb1);
+
+    let test1_actual = fs::read_to_string(&tb1.fpath).expect("Failed to read tb1 file");
+    assert_eq!(test1_output, test1_actual);
+}
+
+#[test]
+fn test_extended_status() {
+    let (mut tb1, _dir) = setup_test_taskbox("test1");
+    let mut tb2 = tb1.sibling("test2");
+
+    let test1_input = r#"# test1
+
+- [/] Task in progress
+- [-] Task cancelled
+- [>] Task forwarded
+- [*] Task starred
+- [ ] Task to cancel
+- [ ] Task not selected
+"#;
+    let test1_output = r#"# test1
+
+- [-] Task cancelled
+- [-] Task to cancel
+- [ ] Task not selected
+"#;
+    let test2_output = r#"# test2
+
+- [/] Task in progress
+- [ ] Task forwarded
+- [*] Task starred
+"#;
+
+    fs::write(&tb1.fpath, test1_input).expect("Failed to write test input to file");
+    tb1.load();
+    assert_eq!(tb1.tasks[0].status, TaskStatus::InProgress);
+    assert_eq!(tb1.tasks[1].status, TaskStatus::Cancelled);
+    assert_eq!(tb1.tasks[2].status, TaskStatus::Forwarded);
+    assert_eq!(tb1.tasks[3].status, TaskStatus::Starred);
+    assert_eq!(tb1.count(), 5);
+
+    tb1.mark_as(find_ids(&tb1, &["Task to cancel"]), TaskStatus::Cancelled, false);
+    tb1.load();
+    assert_eq!(tb1.count(), 4);
+
+    // forwarded one will move even not selected
+    tb1.selected = Some(find_ids(&tb1, &["Task in progress", "Task starred"]));
+    tb2.collect_from(&mut tb1);
+
+    let test1_actual = fs::read_to_string(&tb1.fpath).expect("Failed to read tb1 file");
+    assert_eq!(test1_output, test1_actual);
+
+    let test2_actual = fs::read_to_string(&tb2.fpath).expect("Failed to read tb2 file");
+    assert_eq!(test2_output, test2_actual);
+}
+
+#[test]
+fn test_select_and_mark() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+
+    let test_input = r#"# test
+
+- [x] Task done
+- [ ] deploy to staging
+  - [ ] check logs
+- [ ] deploy to prod
+- [ ] Task other
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+
+    // numbers are the same as `list --numbered`, only uncompeleted ones
+    assert_eq!(tb.select(&TaskSelector::Index(1)), vec![1]);
+    assert_eq!(tb.select(&TaskSelector::Index(2)), vec![2]);
+    assert!(tb.select(&TaskSelector::Index(0)).is_empty());
+    assert!(tb.select(&TaskSelector::Index(5)).is_empty());
+
+    assert_eq!(tb.select(&TaskSelector::Text("Task other".into())), vec![4]);
+    assert!(tb.select(&TaskSelector::Text("Task".into())).is_empty());
+    assert_eq!(tb.select(&TaskSelector::All).len(), 4);
+
+    let ids = tb.select(&TaskSelector::Match(regex::Regex::new("^deploy").unwrap()));
+    assert_eq!(ids, vec![1, 3]);
+
+    tb.mark(ids, true);
+    tb.load();
+    assert_eq!(tb.count(), 2);
+    assert_eq!(tb.select(&TaskSelector::Index(1)), vec![1]);
+    assert_eq!(tb.tasks[1].text, "check logs");
+}
+
+#[test]
+fn test_add_sub() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+
+    let test_input = r#"# test
+
+- [ ] Task one
+  - [x] SubTask1
+  - [ ] SubTask2
+- [ ] Task two
+
+notes
+"#;
+    let test_output = r#"# test
+
+- [ ] Task one
+  - [x] SubTask1
+  - [ ] SubTask2
+  - [ ] SubTask3
+- [ ] Task two
+  - [ ] SubTask1
+
+notes
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+
+    let parents = tb.select(&TaskSelector::parse("/^Task/").unwrap());
+    assert_eq!(parents, vec![0, 3]);
+
+    tb.add_sub("SubTask3".to_string(), &parents[..1], false);
+    tb.add_sub("SubTask2".to_string(), &parents[..1], false); // dup, skip
+    let parents = tb.select(&TaskSelector::parse("4").unwrap());
+    tb.add_sub("SubTask1".to_string(), &parents, false);
+
+    let test_actual = fs::read_to_string(&tb.fpath).expect("Failed to read file");
+    assert_eq!(test_output, test_actual);
+}
+
+#[test]
+fn test_nested_sub_tasks() {
+    let (mut tb1, _dir) = setup_test_taskbox("test1");
+    let mut tb2 = tb1.sibling("test2");
+
+    let test1_input = r#"# test1
+
+- [ ] Task one
+    - [ ] SubTask1
+        - [x] SubSubTask1
+        - [ ] SubSubTask2
+            - [ ] SubSubSubTask1
+    - [ ] SubTask2
+- [ ] Task two
+  - [ ] SubTask1
+    - [ ] SubSubTask1
+"#;
+    let test1_output = r#"# test1
+
+- [x] Task one
+    - [x] SubTask1
+        - [x] SubSubTask1
+"#;
+    let test2_output = r#"# test2
+
+- [ ] Task one
+  - [ ] SubTask1
+    - [ ] SubSubTask2
+      - [ ] SubSubSubTask1
+  - [ ] SubTask2
+- [ ] Task two
+  - [ ] SubSubTask1
+"#;
+
+    fs::write(&tb1.fpath, test1_input).expect("Failed to write test input to file");
+    tb1.load();
+    assert_eq!(tb1.tasks[0].children, vec![1, 5]);
+    assert_eq!(tb1.tasks[1].children, vec![2, 3]);
+    assert_eq!(tb1.tasks[4].parent, Some(3));
+    assert_eq!(tb1.tasks[8].parent, Some(7));
+
+    let items = tb1.get_all_to_mark();
+    assert!(items[3].label.starts_with("    "));
+
+    // remove the middle one, the sub-tree will be lifted
+    tb1.mark(find_ids(&tb1, &["SubTask1"])[1..].to_vec(), true);
+    tb1.load();
+    assert_eq!(tb1.tasks[7].parent, Some(6));
+
+    tb2.collect_from(&mut tb1);
+
+    let test1_actual = fs::read_to_string(&tb1.fpath).expect("Failed to read tb1 file");
+    assert_eq!(test1_output, test1_actual);
+
+    let test2_actual = fs::read_to_string(&tb2.fpath).expect("Failed to read tb2 file");
+    assert_eq!(test2_output, test2_actual);
+}
+
+#[test]
+fn test_collect_selected_with_sub() {
+    let (mut tb1, _dir) = setup_test_taskbox("test1");
+    let mut tb2 = tb1.sibling("test2");
+
+    let test1_input = r#"# test1
+
+- [ ] Task one
+  - [x] SubTask1
+  - [ ] SubTask2
+    - [ ] SubSubTask1
+- [ ] Task two
+  - [ ] SubTask1
+  - [ ] SubTask2
+- [x] Task three
+  - [ ] SubTask1
+- [ ] Task four
+"#;
+    let test1_output = r#"# test1
+
+- [x] Task one
+  - [x] SubTask1
+- [ ] Task two
+  - [ ] SubTask1
+- [x] Task three
+- [ ] Task four
+"#;
+    let test2_output = format!(r#"# test2
+
+- [ ] Task one
+  - [ ] SubTask2
+    - [ ] SubSubTask1
+- [ ] Task two
+  - [ ] SubTask2
+- [ ] {} Task three
+  - [ ] SubTask1
+"#, WARN);
+
+    fs::write(&tb1.fpath, test1_input).expect("Failed to write test input to file");
+    tb1.load();
+
+    // "Task one" with all its sub-tasks, and some sub-tasks without parents
+    tb1.selected = Some(vec![0, 6, 8]);
+    tb2.collect_from(&mut tb1);
+
+    let test1_actual = fs::read_to_string(&tb1.fpath).expect("Failed to read tb1 file");
+    assert_eq!(test1_output, test1_actual);
+
+    let test2_actual = fs::read_to_string(&tb2.fpath).expect("Failed to read tb2 file");
+    assert_eq!(test2_output, test2_actual);
+
+    // the parent copied before will be reused
+    tb1.load();
+    tb1.selected = Some(find_ids(&tb1, &["SubTask1"]));
+    tb2.collect_from(&mut tb1);
+
+    tb2.load();
+    assert_eq!(tb2.tasks.len(), 8);
+    assert_eq!(tb2.tasks[5].text, "SubTask1");
+    assert_eq!(tb2.tasks[5].parent, Some(3));
+}
+
+fn set_box_policy(boxname: &str, policy: SubtaskPolicy) {
+    set_box_config(boxname, BoxConfig { subtask_policy: Some(policy), ..Default::default() });
+}
+
+#[test]
+fn test_subtask_policy_all() {
+    let (mut tb, _dir) = setup_test_taskbox("policy_all");
+    set_box_policy("policy_all", SubtaskPolicy::All);
+
+    let test_input = r#"- [ ] Task one
+  - [ ] SubTask1
+  - [ ] SubTask2
+    - [ ] SubSubTask1
+- [ ] Task two
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+    tb.load();
+
+    tb.mark(find_ids(&tb, &["SubTask1"]), false);
+    tb.load();
+    assert!(!tb.tasks[0].is_done());
+
+    // the last one closes all its ancestors
+    tb.mark(find_ids(&tb, &["SubSubTask1"]), false);
+    tb.load();
+    assert!(tb.tasks[0].is_done());
+    assert!(tb.tasks[2].is_done());
+    assert!(!tb.tasks[4].is_done());
+    unset_box_config("policy_all");
+}
+
+#[test]
+fn test_subtask_policy_independent() {
+    let (mut tb1, _dir) = setup_test_taskbox("policy_indep");
+    set_box_policy("policy_indep", SubtaskPolicy::Independent);
+    let mut tb2 = tb1.sibling("policy_indep2");
+
+    let test1_input = r#"# test1
+
+- [ ] Task one
+  - [x] SubTask1
+  - [ ] SubTask2
+- [x] Task two
+  - [ ] SubTask1
+"#;
+    let test1_output = r#"# test1
+
+- [x] Task two
+"#;
+    let test2_output = r#"# policy_indep2
+
+- [ ] Task one
+  - [x] SubTask1
+  - [ ] SubTask2
+- [x] Task two
+  - [ ] SubTask1
+"#;
+
+    fs::write(&tb1.fpath, test1_input).expect("Failed to write test input to file");
+    tb1.load();
+
+    // no warning item for open sub-tasks under done parents
+    assert!(tb1.get_all_to_mark().iter().all(|item| !item.warn));
+
+    tb2.collect_from(&mut tb1);
+
+    let test1_actual = fs::read_to_string(&tb1.fpath).expect("Failed to read tb1 file");
+    assert_eq!(test1_output, test1_actual);
+
+    let test2_actual = fs::read_to_string(&tb2.fpath).expect("Failed to read tb2 file");
+    assert_eq!(test2_output, test2_actual);
+    unset_box_config("policy_indep");
+}
+
+#[test]
+fn test_to_json() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+    let test_input = r#"# test
+
+- [ ] Task one #work
+  - [x] SubTask1
+  - [/] SubTask2 [󰴹 2026-10-18]
+- [x] Task two
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+
+    let boxjson = tb.to_json(false, true);
+    assert_eq!(boxjson.schema, JSON_SCHEMA_VERSION);
+    assert_eq!(boxjson.name, "test");
+    assert!(!boxjson.encrypted);
+    assert_eq!(boxjson.count, Some(2));
+
+    let tasks = boxjson.tasks.unwrap();
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].tags, vec!["work"]);
+    assert_eq!(tasks[0].children, vec![2]);
+    assert_eq!(tasks[1].status, "in-progress");
+    assert_eq!(tasks[1].parent, Some(0));
+    assert_eq!(tasks[1].depth, 1);
+    assert_eq!(tasks[1].date_stamp.as_deref(), Some("2026-10-18"));
+
+    let boxjson = tb.to_json(true, true);
+    assert_eq!(boxjson.tasks.unwrap().len(), 4);
+    assert!(tb.to_json(false, false).tasks.is_none());
+}
+
+#[test]
+fn test_search() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+    let test_input = r#"# test
+
+- [ ] buy milk
+  - [x] buy bread
+- [x] call mom
+- [/] buy book
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+
+    let re = regex::Regex::new("^buy").unwrap();
+    assert_eq!(tb.search(|t| re.is_match(t), None).len(), 3);
+    assert_eq!(tb.search(|t| re.is_match(t), Some(true)).len(), 1);
+
+    let items = tb.search(|t| fuzzy_match("bk", t), Some(false));
+    assert_eq!(items.iter().map(|item| item.id).collect::<Vec<_>>(), vec![0, 3]);
+}
+
+#[test]
+fn test_tags_and_contexts() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+    let test_input = r#"# test
+
+- [ ] write report #work @office
+  - [ ] draft
+- [ ] buy milk @home
+- [ ] plan #Work
+  - [ ] email to a@b.com
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+    tb.load();
+
+    assert_eq!(tb.tasks[0].tags, vec!["work"]);
+    assert_eq!(tb.tasks[0].contexts, vec!["office"]);
+    assert!(tb.tasks[4].contexts.is_empty());
+
+    let filter = TaskFilter::new(&["#work".into()], &[]);
+    assert_eq!(tb.select(&TaskSelector::Filter(filter.clone())), vec![0, 3]);
+    assert!(tb.has_matched(&filter, false));
+
+    let filter = TaskFilter::new(&["work".into()], &["office".into()]);
+    assert_eq!(tb.select(&TaskSelector::Filter(filter)), vec![0]);
+
+    let filter = TaskFilter::new(&["work".into(), "home".into()], &["garage".into()]);
+    assert_eq!(filter.append_to("fix bike #home"), "fix bike #home #work @garage");
+
+    // "collect --tag" takes the sub-tasks along
+    let mut tb2 = tb.sibling("test2");
+    tb.selected = Some(tb.select(&TaskSelector::Filter(TaskFilter::new(&["work".into()], &[]))));
+    tb2.collect_from(&mut tb);
+
+    tb.load();
+    assert_eq!(tb.tasks.len(), 1);
+    assert_eq!(tb.tasks[0].text, "buy milk @home");
+    tb2.load();
+    assert_eq!(tb2.tasks.len(), 4);
+}
+
+#[test]
+fn test_priority_and_purge_sort() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+    let test_input = r#"# test
+
+- [ ] low one !
+- [ ] plain
+  - [ ] sub plain
+  - [ ] (A) sub urgent
+- [x] done
+- [ ] (B) mid !!!
+"#;
+    let test_output = r#"# test
+
+- [x] done
+- [ ] (B) mid !!!
+- [ ] low one !
+- [ ] plain
+  - [ ] (A) sub urgent
+  - [ ] sub plain
+"#;
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+    tb.load();
+
+    let priorities :Vec<_> = tb.tasks.iter().map(|t| t.priority).collect();
+    assert_eq!(priorities, vec![Some('C'), None, None, Some('A'), None, Some('B')]);
+
+    assert_eq!(with_priority("fix it !!", 'A'), "(A) fix it");
+    assert_eq!(with_priority("(C) fix it", 'B'), "(B) fix it");
+    assert_eq!(with_priority("fix (C) it", 'B'), "(B) fix it");
+
+    tb.purge(true);
+    let test_actual = fs::read_to_string(&tb.fpath).expect("Failed to read file");
+    assert_eq!(test_output, test_actual);
+}
+
+#[test]
+fn test_due_date() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+    tb.add(with_due("pay bill", "2026-10-25"), None, false, "");
+    tb.add("call mom due:2026-13".to_string(), None, false, "");
+
+    tb.load();
+    assert_eq!(tb.tasks[0].text, "pay bill due:2026-10-25");
+    assert_eq!(tb.tasks[0].due.as_deref(), Some("2026-10-25"));
+    assert_eq!(tb.tasks[1].due, None);
+
+    assert_eq!(with_due("pay bill due:2026-10-25 #home", "2026-11-01"), "pay bill #home due:2026-11-01");
+    assert_eq!(tb.to_json(false, true).tasks.unwrap()[0].due.as_deref(), Some("2026-10-25"));
+}
+
+#[test]
+fn test_tickler() {
+    let (mut tb, _dir) = setup_test_taskbox("test");
+    let test_input = format!(r#"# test
+
+- [ ] later t:2999-01-01
+  - [ ] sub of later
+- [ ] now t:{}
+  - [ ] sub of now
+- [ ] normal
+"#, get_today());
+    fs::write(&tb.fpath, test_input).expect("Failed to write test input to file");
+
+    // hidden before the date
+    assert_eq!(tb.count(), 3);
+    assert_eq!(tb.get_all_to_mark().len(), 3);
+    assert_eq!(tb.tasks[0].tickler.as_deref(), Some("2999-01-01"));
+    assert_eq!(with_tickler("later t:2999-01-01 #home", "2999-02-01"), "later #home t:2999-02-01");
+
+    let mut tb_today = tb.sibling(&get_today());
+    tb_today.collect_ticklers();
+
+    let mut tb = TaskBox::new(tb.fpath.clone());
+    tb.load();
+    assert_eq!(tb.tasks.len(), 3);
+    assert_eq!(tb.tasks[2].text, "normal");
+
+    tb_today.load();
+    assert_eq!(tb_today.tasks.len(), 2);
+    assert_eq!(tb_today.tasks[1].text, "sub of now");
+    // not hidden in daily boxes
+    assert_eq!(tb_today.count(), 2);
+}
+
+#[test]
+fn test_checkout_rich_routines() {
+    use chrono::Datelike;
+
+    let (tb, _dir) = setup_test_taskbox("test");
+    let mut today = tb.sibling("today");
+    let mut routine = tb.sibling("routine");
+
+    let weekday = today_date().weekday();
+    routine.add("every 2 days".to_string(), Some(Routine::EveryDays(2)), false, &get_today());
+    routine.add("every 3 days".to_string(), Some(Routine::EveryDays(3)), false, &get_yesterday());
+    routine.add("on weekday".to_string(), Some(Routine::OnWeekdays(vec![weekday])), false, &get_yesterday());
+
+    routine.load();
+    assert!(routine.tasks[0].text.starts_with("{󰃯:2d "));
+    assert_eq!(routine.tasks[0].routine,
+               Some(RoutineInfo::Rule { kind: "2d".into(), start_date: get_today(), opts: RoutineOpts::default() }));
+
+    today.collect_from(&mut routine);
+
+    today.load();
+    assert_eq!(today.tasks.len(), 2);
+    assert!(today.tasks[0].text.starts_with("{󰃯:every-2-days} every 2 days"));
+    assert_eq!(today.tasks[1].routine,
+               Some(RoutineInfo::Checkout { kind: Routine::OnWeekdays(vec![weekday]).name() }));
+}
+
+#[test]
+fn test_routine_end_conditions() {
+    let (tb, _dir) = setup_test_taskbox("test");
+    let mut today = tb.sibling("today");
+    let mut routine = tb.sibling("routine");
+
+    let max = |n| RoutineOpts { times: Some((0, n)), ..Default::default() };
+    routine.add_routine("twice".to_string(), Routine::Daily, &get_yesterday(), &max(2));
+    routine.add_routine("ended".to_string(), Routine::Daily, &get_yesterday(),
+                        &RoutineOpts { until: Some(get_yesterday()), ..Default::default() });
+    routine.add_routine("once more".to_string(), Routine::Daily, &get_yesterday(), &max(1));
+
+    routine.load();
+    assert!(routine.tasks[0].text.contains(" times:0/2 "));
+    let opts = RoutineOpts::parse(" until:2026-12-31 times:1/3 paused:2026-11-01 skip:2026-10-20");
+    assert_eq!(opts.to_string(), " until:2026-12-31 times:1/3 paused:2026-11-01 skip:2026-10-20");
+    assert!(opts.is_paused("2026-11-01") && !opts.is_paused("2026-11-02"));
+    assert!(!opts.is_ended("2026-12-31") && opts.is_ended("2027-01-01"));
+
+    today.collect_from(&mut routine);
+
+    today.load();
+    assert_eq!(today.tasks.len(), 2);
+    assert!(today.tasks[0].text.ends_with(&format!("twice [󰴹 {}]", get_today())));
+    assert!(today.tasks[1].text.starts_with("{󰃯:daily} once more"));
+
+    let mut routine = tb.sibling("routine");
+    routine.load();
+    let Some(RoutineInfo::Rule { opts, .. }) = &routine.tasks[0].routine else { panic!() };
+    assert_eq!(opts.times, Some((1, 2)));
+    assert!(!routine.tasks[0].is_done());
+    // ended by date and by count
+    assert!(routine.tasks[1].is_done());
+    assert!(routine.tasks[2].is_done());
+    assert!(routine.tasks[2].text.contains(" times:1/1 "));
+}
+
+#[test]
+fn test_routine_pause_and_skip() {
+    let (tb, _dir) = setup_test_taskbox("test");
+    let mut today = tb.sibling("today");
+    let mut routine = tb.sibling("routine");
+
+    routine.add("paused".to_string(), Some(Routine::Daily), false, &get_yesterday());
+    routine.add_routine("resumed".to_string(), Routine::Daily, &get_yesterday(),
+                        &RoutineOpts { paused: true, paused_until: Some(get_yesterday()), ..Default::default() });
+    routine.add("skipped".to_string(), Some(Routine::Daily), false, &get_yesterday());
+
+    routine.load();
+    let (paused, skipped) = (routine.tasks[0].id, routine.tasks[2].id);
+    assert_eq!(routine.update_routines(&[paused], |_, _, opts| opts.paused = true), 1);
+    assert_eq!(routine.update_routines(&[skipped], |_, _, opts| opts.skip = Some(get_today())), 1);
+
+    let next = next_routine_date("d", &get_yesterday(), today_date());
+    assert_eq!(next.map(|d| d.to_string()), Some(get_tomorrow()));
+
+    today.collect_from(&mut routine);
+
+    today.load();
+    assert_eq!(today.tasks.len(), 1);
+    assert!(today.tasks[0].text.starts_with("{󰃯:daily} resumed"));
+
+    let mut routine = tb.sibling("routine");
+    routine.load();
+    let opts :Vec<_> = routine.tasks.iter().map(|t| match &t.routine {
+        Some(RoutineInfo::Rule { opts, .. }) => opts.clone(),
+        _ => panic!(),
+    }).collect();
+    assert!(opts[0].paused);
+    assert_eq!(opts[1], RoutineOpts { last: Some(get_today()), ..Default::default() });
+    assert_eq!(opts[2].skip, None);
+    assert!(routine.tasks.iter().all(|t| !t.is_done()));
+}
+
+#[test]
+fn test_routine_catchup() {
+    let (tb, _dir) = setup_test_taskbox("test");
+    let mut routine = tb.sibling("routine");
+
+    // checkout into a past day of its own, to keep each of the missed ones
+    let day = today_date() - chrono::Duration::days(1000);
+    let boxname = day.to_string();
+    set_box_config(&boxname, BoxConfig { routine_coalesce: Some(RoutineCoalesce::Off), ..Default::default() });
+    let mut today = tb.sibling(&boxname);
+
+    let ago = |n| (day - chrono::Duration::days(n)).to_string();
+    let catchup = |how, last| RoutineOpts { catchup: Some(how), last: Some(last), ..Default::default() };
+    routine.add_routine("each".to_string(), Routine::Daily, &ago(5), &catchup(RoutineCatchup::Each, ago(3)));
+    routine.add_routine("collapse".to_string(), Routine::Daily, &ago(5), &catchup(RoutineCatchup::Collapse, ago(3)));
+    routine.add_routine("off".to_string(), Routine::Daily, &ago(5), &catchup(RoutineCatchup::Off, ago(3)));
+    routine.add_routine("weekly".to_string(), Routine::Weekly, &ago(10), &catchup(RoutineCatchup::Collapse, ago(10)));
+
+    today.collect_from(&mut routine);
+
+    today.load();
+    let texts :Vec<_> = today.tasks.iter().map(|t| t.text.clone()).collect();
+    assert_eq!(texts, vec![
+        format!("{{󰃯:daily}} each [󰴹 {}]", ago(2)),
+        format!("{{󰃯:daily}} each [󰴹 {}]", ago(1)),
+        format!("{{󰃯:daily}} each [󰴹 {}]", ago(0)),
+        format!("{{󰃯:daily}} collapse (overdue since {}) [󰴹 {}]", ago(2), ago(0)),
+        format!("{{󰃯:daily}} off [󰴹 {}]", ago(0)),
+        format!("{{󰃯:weekly}} weekly (overdue since {}) [󰴹 {}]", ago(3), ago(3)),
+    ]);
+
+    // the last checkout date is remembered
+    let mut routine = tb.sibling("routine");
+    routine.load();
+    assert!(routine.tasks.iter().all(|t|
+        matches!(&t.routine, Some(RoutineInfo::Rule { opts, .. }) if opts.last == Some(boxname.clone()))));
+
+    // no more for the second time
+    today.collect_from(&mut routine);
+    today.load();
+    assert_eq!(today.tasks.len(), 6);
+    unset_box_config(&boxname);
+}
+
+#[test]
+fn test_routine_occurrences() {
+    let (tb, _dir) = setup_test_taskbox("test");
+    let mut routine = tb.sibling("routine");
+
+    let day = |s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+    routine.add("weekly".to_string(), Some(Routine::Weekly), false, "2026-10-01");
+    routine.add_routine("twice".to_string(), Routine::EveryDays(3), "2026-10-01",
+                        &RoutineOpts { times: Some((1, 3)), ..Default::default() });
+    routine.add_routine("paused".to_string(), Routine::Daily, "2026-10-01",
+                        &RoutineOpts { paused: true, paused_until: Some("2026-10-14".into()),
+                                       skip: Some("2026-10-15".into()), ..Default::default() });
+
+    let occurs = routine.occurrences(day("2026-10-08"), 9);
+    let occurs :Vec<_> = occurs.iter().map(|o| (o.date.as_str(), o.text.as_str())).collect();
+    assert_eq!(occurs, vec![
+        ("2026-10-08", "weekly"),
+        ("2026-10-10", "twice"),
+        ("2026-10-13", "twice"),
+        ("2026-10-15", "weekly"),
+        ("2026-10-16", "paused"),
+    ]);
+    assert_eq!(routine.occurrences(day("2026-10-08"), 0).len(), 0);
+}
+
+#[test]
+fn test_week_and_month_boxes() {
+    let (tb, _dir) = setup_test_taskbox("test");
+    let today = today_date();
+
+    let week = tb.sibling("thisweek");
+    assert_eq!(week.tbname, period_box_name(BoxLevel::Week, today));
+    assert_eq!(week.alias.as_deref(), Some("thisweek"));
+    let month = tb.sibling("thismonth");
+    assert_eq!(month.tbname, period_box_name(BoxLevel::Month, today));
+    assert_eq!(month.alias.as_deref(), Some("thismonth"));
+
+    // not hidden in week boxes
+    fs::write(&week.fpath, "- [ ] later t:2999-01-01\n").expect("write err");
+    let mut week = TaskBox::new(week.fpath.clone());
+    assert_eq!(week.count(), 1);
+
+    // sink from the old week box
+    let old_path = tb.sibling("2000-W01").fpath;
+    fs::write(&old_path, "- [ ] old one\n").expect("write err");
+    week.collect_from(&mut TaskBox::new(old_path));
+    week.load();
+    assert_eq!(week.tasks.len(), 2);
+    assert_eq!(week.tasks[1].text, "old one");
+}
+
+#[test]
+fn test_clock_override() {
+    let now = |s| parse_now(s).unwrap();
+
+    set_now(Some(now("2028-02-29 10:00")));
+    assert_eq!(get_today(), "2028-02-29");
+    assert_eq!(get_tomorrow(), "2028-03-01");
+    assert_eq!(get_box_alias("2028-02-28"), "yesterday");
+    assert_eq!(get_box_unalias("thismonth"), "2028-02");
+    assert!(match_routine("monthly", "2028-01-31", "today"));
+    assert!(match_routine("yearly", "2024-02-29", "today"));
+    assert!(!match_routine("last fri", "2028-01-01", "today"));
+
+    // month ends are clamped, without drifting
+    set_now(Some(now("2028-03-31 10:00")));
+    assert!(match_routine("monthly", "2028-01-31", "today"));
+    assert!(!match_routine("monthly", "2028-01-31", "2028-03-29"));
+    assert!(match_routine("last fri", "2028-01-01", "today"));
+    set_now(Some(now("2027-02-28 10:00")));
+    assert!(match_routine("yearly", "2024-02-29", "today"));
+
+    set_now(None);
+    assert_eq!(get_today(), today_date().to_string());
+}
+
+#[test]
+fn test_simulate_month_by_env() {
+    let (dir, todor) = setup_test_cli();
+
+    todor(Some("2028-02-01"), &["add", "-r", "monthly", "--start", "2028-01-31", "pay rent"]);
+    todor(Some("2028-02-01"), &["add", "-r", "last fri", "--start", "2028-01-01", "review"]);
+    todor(Some("2028-02-01"), &["-i", "today", "add", "read book"]);
+
+    // a checkout and sink every morning, through the leap day
+    let mut date = chrono::NaiveDate::from_ymd_opt(2028, 2, 1).unwrap();
+    while date <= chrono::NaiveDate::from_ymd_opt(2028, 3, 1).unwrap() {
+        let now = format!("{} 08:00", date);
+        todor(Some(&now), &["sink"]);
+        todor(Some(&now), &["checkout"]);
+        date = date.succ_opt().unwrap();
+    }
+
+    let boxfile = |name: &str| fs::read_to_string(dir.path().join(name).with_extension("md")).unwrap_or_default();
+    // sunk into the next day, with the date of occurrence
+    assert!(!boxfile("2028-02-29").contains("- [ ]"));
+    let mar01 = boxfile("2028-03-01");
+    assert!(mar01.contains("pay rent [󰴹 2028-02-29]"));
+    assert!(mar01.contains("review [󰴹 2028-02-25]"));
+    assert!(mar01.contains("read book"));
+    let routines = boxfile("ROUTINES");
+    assert!(routines.contains("last:2028-02-29"));
+    assert!(routines.contains("last:2028-02-25"));
+}
+
+#[test]
+fn test_journal_undo() {
+    use todor::journal;
+    let (mut tb, dir) = setup_test_taskbox("test");
+    let content = |tb: &TaskBox| fs::read_to_string(&tb.fpath).unwrap();
+
+    let _rec = journal::start(dir.path(), "todor add");
+    tb.add("Task 1".to_string(), None, false, "");
+    assert!(journal::finish().unwrap().is_some());
+    let after_add = content(&tb);
+
+    let _rec = journal::start(dir.path(), "todor mark");
+    tb.mark(find_ids(&tb, &["Task 1"]), false);
+    let mut other = tb.sibling("other");
+    other.add("Task 2".to_string(), None, false, "");
+    journal::finish().unwrap();
+
+    // nothing changed, nothing recorded
+    let _rec = journal::start(dir.path(), "todor list");
+    tb.load();
+    assert!(journal::finish().unwrap().is_none());
+
+    let history = journal::history(dir.path());
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].command, "todor mark");
+    assert_eq!(history[0].boxes(), vec!["test", "other"]);
+
+    // refused if changed since
+    fs::write(&tb.fpath, "# test\n").expect("write err");
+    assert!(journal::undo(dir.path(), 1).is_err());
+
+    let _rec = journal::start(dir.path(), "todor edit");
+    journal::track(&tb.fpath);
+    fs::write(&tb.fpath, &after_add).expect("write err");
+    journal::finish().unwrap();
+    fs::write(&tb.fpath, content(&tb).replace("- [ ]", "- [x]")).expect("write err");
+    assert!(journal::undo(dir.path(), 1).is_err());
+    fs::write(&tb.fpath, &after_add).expect("write err");
+
+    // back to the very beginning
+    let undone = journal::undo(dir.path(), 9).unwrap();
+    assert_eq!(undone.len(), 3);
+    assert!(!tb.fpath.exists());
+    assert!(!other.fpath.exists());
+    assert!(journal::history(dir.path()).is_empty());
+}
+
+#[test]
+fn test_journal_forget() {
+    use todor::journal;
+    let (mut tb, dir) = setup_test_taskbox("secret");
+    let other = tb.sibling("other");
+
+    let _rec = journal::start(dir.path(), "todor add");
+    tb.add("Task 1".to_string(), None, false, "");
+    journal::track(&other.fpath);
+    fs::write(&other.fpath, "# other\n").expect("write err");
+    journal::finish().unwrap();
+
+    journal::forget(dir.path(), &tb.fpath).unwrap();
+    let history = journal::history(dir.path());
+    assert_eq!(history[0].boxes(), vec!["other"]);
+    let edir = dir.path().join(journal::JOURNAL_DIR).join(format!("{:06}", history[0].seq));
+    assert_eq!(fs::read_to_string(edir.join("0.after")).unwrap(), "# other\n");
+    assert!(!edir.join("1.after").exists());
+
+    journal::forget(dir.path(), &other.fpath).unwrap();
+    assert!(journal::history(dir.path()).is_empty());
+}
+
+#[test]
+fn test_journal_undo_by_cli() {
+    let (dir, todor) = setup_test_cli();
+    let todor = |args: &[&str]| todor(None, args);
+    let inbox = || fs::read_to_string(dir.path().join("INBOX.md")).unwrap();
+
+    todor(&["add", "Task 1"]);
+    todor(&["add", "Task 2"]);
+    let before = inbox();
+
+    todor(&["mark", "1"]);
+    assert_ne!(inbox(), before);
+    todor(&["undo"]);
+    assert_eq!(inbox(), before);
+
+    todor(&["undo", "2"]);
+    assert!(!dir.path().join("INBOX.md").exists());
+}
+
+#[test]
+fn test_skip_next_today() {
+    let (dir, todor) = setup_test_cli();
+    let todor = |args: &[&str]| todor(Some("2028-02-01 09:00"), args);
+    let routines = || fs::read_to_string(dir.path().join("ROUTINES.md")).unwrap();
+
+    // today's one if not checked out yet
+    todor(&["add", "-r", "daily", "--start", "2028-01-31", "-n", "water"]);
+    todor(&["routine", "skip-next", "1"]);
+    assert!(routines().contains("skip:2028-02-01"));
+    todor(&["checkout"]);
+    assert!(!fs::read_to_string(dir.path().join("2028-02-01.md")).unwrap().contains("water"));
+
+    // or the next one after today
+    todor(&["routine", "skip-next", "1"]);
+    assert!(routines().contains("skip:2028-02-02"));
+}
+
+#[test]
+fn test_checkout_fresh_days_by_cli() {
+    let (dir, todor) = setup_test_cli();
+    let boxfile = |name: &str| fs::read_to_string(dir.path().join(name).with_extension("md")).unwrap_or_default();
+
+    // the rule updated by the daily hook of the new box is not overwritten
+    todor(Some("2028-01-03"), &["add", "-r", "daily", "--start", "2028-01-03", "--max", "2", "-n", "water"]);
+    for date in ["2028-01-03", "2028-01-04", "2028-01-05", "2028-01-06"] {
+        todor(Some(date), &["checkout"]);
+    }
+    assert!(boxfile("ROUTINES").contains("- [x] {󰃯:d 2028-01-03"));
+    assert!(boxfile("ROUTINES").contains("times:2/2 last:2028-01-04"));
+    assert!(boxfile("2028-01-04").contains("water"));
+    assert!(!boxfile("2028-01-05").contains("water"));
+}
+
+#[test]
+fn test_catchup_fresh_box_by_cli() {
+    let (dir, todor) = setup_test_cli();
+
+    // the missed one is checked out only once
+    todor(Some("2028-01-03"), &["add", "-r", "weekly", "--start", "2028-01-03", "--catchup", "each", "-n", "review"]);
+    todor(Some("2028-01-03"), &["checkout"]);
+    todor(Some("2028-01-17"), &["checkout"]);
+    let content = fs::read_to_string(dir.path().join("2028-01-17.md")).unwrap();
+    assert!(content.contains("{󰃯:weekly} review (missed 1 time) [󰴹 2028-01-17]"));
+}
+
+#[test]
+fn test_json_on_first_load_by_cli() {
+    let (_dir, todor) = setup_test_cli();
+    todor(None, &["add", "-r", "daily", "-n", "water"]);
+
+    // no output of the daily hook mixed in
+    let boxjson :serde_json::Value = serde_json::from_str(&todor(None, &["-i", "today", "list", "--format", "json"])).unwrap();
+    assert_eq!(boxjson["box"], get_today());
+    assert_eq!(boxjson["tasks"].as_array().unwrap().len(), 1);
+
+    let countjson :serde_json::Value = serde_json::from_str(&todor(None, &["-i", "tomorrow", "count", "--format", "json"])).unwrap();
+    assert_eq!(countjson["count"], 1);
+
+    for line in todor(None, &["-i", "+2d", "list", "--format", "ndjson"]).lines() {
+        serde_json::from_str::<serde_json::Value>(line).unwrap();
+    }
+    let listbox :serde_json::Value = serde_json::from_str(&todor(None, &["listbox", "--format", "json"])).unwrap();
+    assert!(listbox["boxes"].is_array());
+}
+
+#[test]
+fn test_ics_stamp_by_clock() {
+    let (_dir, todor) = setup_test_cli();
+    todor(Some("2028-02-01 09:00"), &["add", "-r", "daily", "--start", "2028-02-01", "-n", "water"]);
+
+    let ics = todor(Some("2028-02-01 09:00"), &["routines", "--upcoming", "--days", "1", "--ics"]);
+    let stamp = parse_now("2028-02-01 09:00").unwrap().and_local_timezone(chrono::Local).unwrap()
+                    .with_timezone(&chrono::Utc).format("%Y%m%dT%H%M%SZ").to_string();
+    assert!(ics.contains(&format!("DTSTAMP:{}\r\n", stamp)));
+}